@@ -0,0 +1,239 @@
+//! Plays generated games with bots and reports how each strategy fares per board size and density.
+//!
+//! `hex-minesweeper-sim [--games N] [--sizes 3,4,5] [--densities 0.1,0.2]
+//! [--strategies random,solver,probability] [--first-click none|tile|opening|no-guess]`
+
+use hex_minesweeper::model::{
+    BoardShape, BoardSource, FirstClickSafety, Game, GameRule, MineCount, WinRule,
+};
+use hex_minesweeper::strategy::{
+    self, PlayReport, ProbabilityStrategy, RandomStrategy, SolverStrategy, Strategy,
+};
+use std::process;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    games: usize,
+    sizes: Vec<usize>,
+    densities: Vec<f32>,
+    strategies: Vec<String>,
+    first_click: FirstClickSafety,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            games: 1000,
+            sizes: vec![3, 4, 5],
+            densities: vec![0.1, 0.15, 0.2, 0.25],
+            strategies: vec![
+                "random".to_string(),
+                "solver".to_string(),
+                "probability".to_string(),
+            ],
+            first_click: FirstClickSafety::Opening,
+        }
+    }
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+            match flag.as_str() {
+                "--games" => options.games = parse_value(&flag, &value)?,
+                "--sizes" => options.sizes = parse_list(&flag, &value)?,
+                "--densities" => options.densities = parse_list(&flag, &value)?,
+                "--strategies" => {
+                    options.strategies = value.split(',').map(str::to_string).collect();
+                    for name in &options.strategies {
+                        new_strategy(name, 0)?;
+                    }
+                }
+                "--first-click" => {
+                    options.first_click = match value.as_str() {
+                        "none" => FirstClickSafety::None,
+                        "tile" => FirstClickSafety::Tile,
+                        "opening" => FirstClickSafety::Opening,
+                        "no-guess" => FirstClickSafety::NoGuess,
+                        _ => return Err(format!("unknown first click safety {}", value)),
+                    }
+                }
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+        if let Some(size) = options
+            .sizes
+            .iter()
+            .find(|size| !BoardShape::hexagon(**size).is_valid())
+        {
+            return Err(format!("invalid board size {}", size));
+        }
+        if let Some(density) = options
+            .densities
+            .iter()
+            .find(|density| !(0.0..=1.0).contains(*density))
+        {
+            return Err(format!("densities must be from 0 to 1, not {}", density));
+        }
+        Ok(options)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_list<T: std::str::FromStr>(flag: &str, value: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|item| parse_value(flag, item))
+        .collect()
+}
+
+fn new_strategy(name: &str, seed: u64) -> Result<Box<dyn Strategy>, String> {
+    match name {
+        "random" => Ok(Box::new(RandomStrategy::new(seed))),
+        "solver" => Ok(Box::new(SolverStrategy::new(seed))),
+        "probability" => Ok(Box::new(ProbabilityStrategy::new(seed))),
+        _ => Err(format!("unknown strategy {}", name)),
+    }
+}
+
+/// Totals over the games of one strategy, board size and density.
+#[derive(Debug, Default, Clone, Copy)]
+struct Summary {
+    games: usize,
+    wins: usize,
+    guesses: usize,
+    duration: Duration,
+}
+
+impl Summary {
+    fn add(&mut self, report: PlayReport) {
+        self.games += 1;
+        self.wins += report.is_won as usize;
+        self.guesses += report.guesses;
+        self.duration += report.duration;
+    }
+
+    fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games.max(1) as f64
+    }
+
+    fn average_guesses(&self) -> f64 {
+        self.guesses as f64 / self.games.max(1) as f64
+    }
+
+    fn average_duration(&self) -> Duration {
+        self.duration / self.games.max(1) as u32
+    }
+}
+
+fn simulate(options: &Options, name: &str, size: usize, density: f32) -> Summary {
+    let mut summary = Summary::default();
+    let rule = GameRule {
+        win_rule: WinRule::SafeTilesOpened,
+        ..Default::default()
+    };
+    for i in 0..options.games {
+        // 同じ番号のゲームはどの戦略でも同じ盤面になる
+        let source = BoardSource::Generate {
+            shape: BoardShape::hexagon(size),
+            mine_count: MineCount::Density(density),
+            seed: Some(format!("sim-{}-{}-{}", size, density, i)),
+            first_click: options.first_click,
+        };
+        let mut game =
+            Game::from_source(&source, rule.clone()).expect("generated boards are always valid");
+        let mut strategy =
+            new_strategy(name, i as u64).expect("strategies are checked when parsed");
+        summary.add(strategy::play(&mut game, strategy.as_mut()));
+    }
+    summary
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!(
+                "usage: hex-minesweeper-sim [--games N] [--sizes 3,4,5] [--densities 0.1,0.2] \
+                 [--strategies random,solver,probability] [--first-click none|tile|opening|no-guess]"
+            );
+            process::exit(2);
+        }
+    };
+
+    println!(
+        "{:<12} {:>4} {:>7} {:>6} {:>8} {:>8} {:>10}",
+        "strategy", "size", "density", "games", "win rate", "guesses", "ms/game"
+    );
+    for name in &options.strategies {
+        for &size in &options.sizes {
+            for &density in &options.densities {
+                let summary = simulate(&options, name, size, density);
+                println!(
+                    "{:<12} {:>4} {:>7.2} {:>6} {:>7.1}% {:>8.2} {:>10.3}",
+                    name,
+                    size,
+                    density,
+                    summary.games,
+                    summary.win_rate() * 100.0,
+                    summary.average_guesses(),
+                    summary.average_duration().as_secs_f64() * 1000.0
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(Options::parse(args("")), Ok(Options::default()));
+        let options = Options::parse(args(
+            "--games 10 --sizes 2,6 --densities 0.3 --strategies solver --first-click no-guess",
+        ))
+        .unwrap();
+        assert_eq!(options.games, 10);
+        assert_eq!(options.sizes, vec![2, 6]);
+        assert_eq!(options.densities, vec![0.3]);
+        assert_eq!(options.strategies, vec!["solver".to_string()]);
+        assert_eq!(options.first_click, FirstClickSafety::NoGuess);
+
+        assert!(Options::parse(args("--games")).is_err());
+        assert!(Options::parse(args("--sizes 0")).is_err());
+        assert!(Options::parse(args("--sizes 3,1000000")).is_err());
+        assert!(Options::parse(args("--densities 0.2,1.5")).is_err());
+        assert!(Options::parse(args("--densities -0.1")).is_err());
+        assert!(Options::parse(args("--densities NaN")).is_err());
+        assert!(Options::parse(args("--strategies cheat")).is_err());
+    }
+
+    #[test]
+    fn test_simulate() {
+        let options = Options {
+            games: 5,
+            ..Default::default()
+        };
+        let summary = simulate(&options, "probability", 3, 0.1);
+        assert_eq!(summary.games, 5);
+        assert!(summary.wins <= 5);
+    }
+}
@@ -1,10 +1,14 @@
 use crate::events::*;
 use crate::hexgrid;
-use crate::model::GameBoard;
+use crate::level::Levels;
+use crate::model::{BoardShape, GameBoard, MineCount};
+use crate::preset::{CustomField, DifficultyMenu, MAX_LIVES, PRESETS};
+use crate::replay::{OnPlayerInput, ReplayAction, ReplayPlayer};
 use crate::scene::GameScene;
-use crate::view::Tilemap;
+use crate::view::{tilemap_point, Tilemap};
 use crate::{Config, CursorWorldPosition};
 use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
 
 pub struct ControllerPlugin;
 
@@ -12,13 +16,24 @@ impl Plugin for ControllerPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(check_try_move_tile_system)
             .add_system(check_retry_system)
-            .add_system(check_try_undo_system);
+            .add_system(check_try_undo_system)
+            .add_system(check_try_redo_system)
+            .add_system(check_level_select_system)
+            .add_system(check_difficulty_menu_system)
+            .add_system(check_next_level_system)
+            .add_system(check_replay_system)
+            .add_system(check_try_hint_system)
+            .add_system(check_toggle_heatmap_system)
+            .add_system(check_quit_game_system);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn check_try_move_tile_system(
     mut on_try_open_tile_writer: EventWriter<OnTryOpenTile>,
     mut on_try_flag_tile_writer: EventWriter<OnTryFlagTile>,
+    mut on_try_chord_tile_writer: EventWriter<OnTryChordTile>,
+    mut on_player_input_writer: EventWriter<OnPlayerInput>,
     cursor_world_position: Res<CursorWorldPosition>,
     game_board: Res<GameBoard>,
     tilemap_query: Query<&Transform, With<Tilemap>>,
@@ -26,18 +41,38 @@ fn check_try_move_tile_system(
     config: Res<Config>,
     game_scene: Res<GameScene>,
 ) {
-    let tilemap_transform = tilemap_query.single();
-    let grid = hexgrid::cartesian_point_to_nearest_pointy_hex_grid(
-        (cursor_world_position.position - tilemap_transform.translation.truncate())
-            / config.tile_size,
-    );
+    let grid = hexgrid::cartesian_point_to_nearest_pointy_hex_grid(tilemap_point(
+        tilemap_query.single(),
+        cursor_world_position.position,
+        &config,
+    ));
 
     if let GameScene::InGame = *game_scene {
         if !game_board.is_out_of_bound(grid) && buttons.just_released(MouseButton::Left) {
-            on_try_open_tile_writer.send(OnTryOpenTile { target: grid });
+            // 開いている数字をクリックしたら周りをまとめて開ける
+            if game_board.get(grid).is_some_and(|t| t.is_open()) {
+                on_try_chord_tile_writer.send(OnTryChordTile { target: grid });
+                on_player_input_writer.send(OnPlayerInput {
+                    action: ReplayAction::Chord { target: grid },
+                });
+            } else {
+                on_try_open_tile_writer.send(OnTryOpenTile { target: grid });
+                on_player_input_writer.send(OnPlayerInput {
+                    action: ReplayAction::Open { target: grid },
+                });
+            }
+        }
+        if !game_board.is_out_of_bound(grid) && buttons.just_released(MouseButton::Middle) {
+            on_try_chord_tile_writer.send(OnTryChordTile { target: grid });
+            on_player_input_writer.send(OnPlayerInput {
+                action: ReplayAction::Chord { target: grid },
+            });
         }
         if !game_board.is_out_of_bound(grid) && buttons.just_released(MouseButton::Right) {
             on_try_flag_tile_writer.send(OnTryFlagTile { target: grid });
+            on_player_input_writer.send(OnPlayerInput {
+                action: ReplayAction::Flag { target: grid },
+            });
         }
     }
 }
@@ -46,27 +81,234 @@ fn check_retry_system(
     mut game_scene: ResMut<GameScene>,
     buttons: Res<Input<MouseButton>>,
     mut writer: EventWriter<OnRetry>,
+) {
+    if let GameScene::Over | GameScene::Clear = *game_scene {
+        if buttons.just_released(MouseButton::Left) {
+            *game_scene = GameScene::InGame;
+            writer.send(OnRetry::SameBoard);
+        } else if buttons.just_released(MouseButton::Right) {
+            *game_scene = GameScene::InGame;
+            writer.send(OnRetry::NewBoard);
+        }
+    }
+}
+
+fn check_try_undo_system(
+    game_scene: Res<GameScene>,
+    mut writer: EventWriter<OnTryUndo>,
+    mut on_player_input_writer: EventWriter<OnPlayerInput>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let can_undo = match *game_scene {
+        // ゲームオーバー後は練習モードでなければモデルが断る
+        GameScene::InGame | GameScene::Over => true,
+        GameScene::Clear
+        | GameScene::LevelSelect
+        | GameScene::DifficultyMenu
+        | GameScene::Replay => false,
+    };
+    if can_undo
+        && keys.pressed(KeyCode::LControl)
+        && !keys.pressed(KeyCode::LShift)
+        && keys.just_released(KeyCode::Z)
+    {
+        writer.send(OnTryUndo);
+        on_player_input_writer.send(OnPlayerInput {
+            action: ReplayAction::Undo,
+        });
+    }
+}
+
+fn check_try_hint_system(
+    game_scene: Res<GameScene>,
+    mut writer: EventWriter<OnTryHint>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if let GameScene::InGame = *game_scene {
+        if keys.just_released(KeyCode::H) {
+            writer.send(OnTryHint);
+        }
+    }
+}
+
+fn check_toggle_heatmap_system(
+    game_scene: Res<GameScene>,
+    mut writer: EventWriter<OnToggleHeatmap>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let can_toggle = match *game_scene {
+        GameScene::InGame | GameScene::Over | GameScene::Clear | GameScene::Replay => true,
+        GameScene::LevelSelect | GameScene::DifficultyMenu => false,
+    };
+    if can_toggle && keys.just_released(KeyCode::M) {
+        writer.send(OnToggleHeatmap);
+    }
+}
+
+fn check_try_redo_system(
+    game_scene: Res<GameScene>,
+    mut writer: EventWriter<OnTryRedo>,
+    mut on_player_input_writer: EventWriter<OnPlayerInput>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if let GameScene::InGame = *game_scene {
+        if keys.pressed(KeyCode::LControl)
+            && (keys.just_released(KeyCode::Y)
+                || keys.pressed(KeyCode::LShift) && keys.just_released(KeyCode::Z))
+        {
+            writer.send(OnTryRedo);
+            on_player_input_writer.send(OnPlayerInput {
+                action: ReplayAction::Redo,
+            });
+        }
+    }
+}
+
+fn check_level_select_system(
+    mut game_scene: ResMut<GameScene>,
+    mut levels: ResMut<Levels>,
+    mut writer: EventWriter<OnSelectLevel>,
+    keys: Res<Input<KeyCode>>,
 ) {
     match *game_scene {
-        GameScene::Over | GameScene::Clear => {
-            if buttons.just_released(MouseButton::Left) || buttons.just_released(MouseButton::Right)
-            {
+        GameScene::InGame if keys.just_released(KeyCode::L) => {
+            *game_scene = GameScene::LevelSelect;
+        }
+        GameScene::LevelSelect => {
+            if keys.just_released(KeyCode::Escape) || keys.just_released(KeyCode::L) {
                 *game_scene = GameScene::InGame;
-                writer.send(OnRetry);
+            } else if keys.just_released(KeyCode::Up) && levels.cursor > 0 {
+                levels.cursor -= 1;
+            } else if keys.just_released(KeyCode::Down) && levels.cursor + 1 < levels.boards.len() {
+                levels.cursor += 1;
+            } else if keys.just_released(KeyCode::Return) && !levels.boards.is_empty() {
+                writer.send(OnSelectLevel {
+                    index: levels.cursor,
+                });
             }
         }
         _ => {}
     }
 }
 
-fn check_try_undo_system(
+fn check_difficulty_menu_system(
+    mut game_scene: ResMut<GameScene>,
+    mut menu: ResMut<DifficultyMenu>,
+    mut writer: EventWriter<OnSelectDifficulty>,
+    keys: Res<Input<KeyCode>>,
+) {
+    match *game_scene {
+        GameScene::InGame if keys.just_released(KeyCode::D) => {
+            *game_scene = GameScene::DifficultyMenu;
+        }
+        GameScene::DifficultyMenu => match menu.custom_field {
+            None => {
+                if keys.just_released(KeyCode::Escape) || keys.just_released(KeyCode::D) {
+                    *game_scene = GameScene::InGame;
+                } else if keys.just_released(KeyCode::Up) && menu.cursor > 0 {
+                    menu.cursor -= 1;
+                } else if keys.just_released(KeyCode::Down) && menu.cursor < PRESETS.len() {
+                    menu.cursor += 1;
+                } else if keys.just_released(KeyCode::G) {
+                    menu.is_no_guess = !menu.is_no_guess;
+                } else if keys.just_released(KeyCode::P) {
+                    menu.rule.practice = !menu.rule.practice;
+                } else if keys.just_released(KeyCode::L) {
+                    menu.rule.lives = menu.rule.lives % MAX_LIVES + 1;
+                } else if keys.just_released(KeyCode::F) {
+                    menu.rule.strict_flag = !menu.rule.strict_flag;
+                } else if keys.just_released(KeyCode::W) {
+                    menu.rule.win_rule = menu.rule.win_rule.next();
+                } else if keys.just_released(KeyCode::Return) {
+                    match PRESETS.get(menu.cursor) {
+                        Some(preset) => writer.send(OnSelectDifficulty {
+                            shape: BoardShape::hexagon(preset.tiles_per_side),
+                            mine_count: MineCount::Density(preset.density),
+                            is_no_guess: menu.is_no_guess,
+                            rule: menu.rule.clone(),
+                        }),
+                        None => menu.custom_field = Some(CustomField::Shape),
+                    }
+                }
+            }
+            // カスタムの設定
+            Some(field) => {
+                if keys.just_released(KeyCode::Escape) {
+                    menu.custom_field = None;
+                } else if keys.just_released(KeyCode::Up) || keys.just_released(KeyCode::Down) {
+                    let fields = menu.custom.fields();
+                    let index = fields.iter().position(|f| *f == field).unwrap_or_default();
+                    let index = if keys.just_released(KeyCode::Down) {
+                        index + 1
+                    } else {
+                        index + fields.len() - 1
+                    };
+                    menu.custom_field = Some(fields[index % fields.len()]);
+                } else if keys.just_released(KeyCode::Left) || keys.just_released(KeyCode::Right) {
+                    menu.custom
+                        .adjust(field, keys.just_released(KeyCode::Right));
+                } else if keys.just_released(KeyCode::Return) && menu.custom.check().is_ok() {
+                    menu.custom_field = None;
+                    writer.send(OnSelectDifficulty {
+                        shape: menu.custom.shape(),
+                        mine_count: MineCount::Exact(menu.custom.mine_count),
+                        is_no_guess: menu.is_no_guess,
+                        rule: menu.rule.clone(),
+                    });
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+fn check_next_level_system(
     game_scene: Res<GameScene>,
-    mut writer: EventWriter<OnTryUndo>,
+    levels: Res<Levels>,
+    mut writer: EventWriter<OnSelectLevel>,
     keys: Res<Input<KeyCode>>,
 ) {
-    if let GameScene::InGame = *game_scene {
-        if keys.pressed(KeyCode::LControl) && keys.just_released(KeyCode::Z) {
-            writer.send(OnTryUndo);
+    if let GameScene::Clear = *game_scene {
+        if keys.just_released(KeyCode::N) {
+            if let Some(index) = levels.next() {
+                writer.send(OnSelectLevel { index });
+            }
         }
     }
 }
+
+fn check_replay_system(
+    game_scene: Res<GameScene>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut on_start_replay_writer: EventWriter<OnStartReplay>,
+    mut on_stop_replay_writer: EventWriter<OnStopReplay>,
+    keys: Res<Input<KeyCode>>,
+) {
+    match *game_scene {
+        // replay.ron はゲームが終わったときに書くので，遊んでいる途中に再生すると今のゲームが消える
+        GameScene::Over | GameScene::Clear if keys.just_released(KeyCode::P) => {
+            on_start_replay_writer.send(OnStartReplay);
+        }
+        GameScene::Replay => {
+            if keys.just_released(KeyCode::Escape) {
+                on_stop_replay_writer.send(OnStopReplay);
+            } else if keys.just_released(KeyCode::Space) {
+                replay_player.is_paused = !replay_player.is_paused;
+            } else if keys.just_released(KeyCode::Up) {
+                replay_player.speed_up();
+            } else if keys.just_released(KeyCode::Down) {
+                replay_player.slow_down();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_quit_game_system(
+    mut reader: EventReader<WindowCloseRequested>,
+    mut writer: EventWriter<OnQuitGame>,
+) {
+    if reader.iter().last().is_some() {
+        writer.send(OnQuitGame);
+    }
+}
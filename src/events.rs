@@ -1,5 +1,7 @@
 use crate::hexgrid::PointyHexGrid;
+use crate::model::{BoardShape, GameRule, MineCount};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub struct OnTryOpenTile {
@@ -11,22 +13,80 @@ pub struct OnTryFlagTile {
     pub target: PointyHexGrid,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
+pub struct OnTryChordTile {
+    pub target: PointyHexGrid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OnMoveTile {
-    Open { target: PointyHexGrid },
-    Flag { target: PointyHexGrid },
+    Open {
+        target: PointyHexGrid,
+    },
+    /// Several tiles opened by one action, e.g. a cascade from a zero tile.
+    OpenRegion {
+        targets: Vec<PointyHexGrid>,
+    },
+    Flag {
+        target: PointyHexGrid,
+    },
+    /// Turns a flag into a question mark.
+    Question {
+        target: PointyHexGrid,
+    },
+    /// Removes a question mark.
+    ClearMark {
+        target: PointyHexGrid,
+    },
+}
+
+impl OnMoveTile {
+    pub fn targets(&self) -> &[PointyHexGrid] {
+        match self {
+            Self::OpenRegion { targets } => targets,
+            Self::Open { target }
+            | Self::Flag { target }
+            | Self::Question { target }
+            | Self::ClearMark { target } => std::slice::from_ref(target),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct OnTryUndo;
 
 #[derive(Debug, Clone, Copy)]
+pub struct OnTryRedo;
+
+/// Sent along with the `OnMoveTile` of a move played again by redo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnRedoTile {
+    pub move_tile: OnMoveTile,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum OnUndoTile {
     UnOpen { target: PointyHexGrid },
+    UnOpenRegion { targets: Vec<PointyHexGrid> },
     UnFlag { target: PointyHexGrid },
+    UnQuestion { target: PointyHexGrid },
+    UnClearMark { target: PointyHexGrid },
 }
 
-#[derive(Debug)]
+impl OnUndoTile {
+    pub fn targets(&self) -> &[PointyHexGrid] {
+        match self {
+            Self::UnOpenRegion { targets } => targets,
+            Self::UnOpen { target }
+            | Self::UnFlag { target }
+            | Self::UnQuestion { target }
+            | Self::UnClearMark { target } => std::slice::from_ref(target),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OnGameOver {
     Open { target: PointyHexGrid },
     Flag { target: PointyHexGrid },
@@ -34,11 +94,63 @@ pub enum OnGameOver {
 #[derive(Debug)]
 pub struct OnGameClear;
 
+/// Sent when the losing move is undone in practice mode.
+#[derive(Debug)]
+pub struct OnRescue;
+
 #[derive(Debug)]
 pub struct OnQuitGame;
 
 #[derive(Debug)]
-pub struct OnRetry;
+pub enum OnRetry {
+    /// Play the same layout again from the start.
+    SameBoard,
+    /// Play a freshly generated board.
+    NewBoard,
+}
+
+/// Index into `Levels::boards`.
+#[derive(Debug)]
+pub struct OnSelectLevel {
+    pub index: usize,
+}
+
+/// Sent after the `GameBoard` resource has been reset or replaced.
+#[derive(Debug)]
+pub struct OnResetBoard;
+
+/// Starts playing back the last recorded replay.
+#[derive(Debug)]
+pub struct OnStartReplay;
+
+/// Stops the playback and hands the board over to the player.
+#[derive(Debug)]
+pub struct OnStopReplay;
+
+/// Asks the solver for a move the visible board forces.
+#[derive(Debug)]
+pub struct OnTryHint;
+
+/// A forced move: `target` is safe to open, or a mine to flag if `is_mine`.
+#[derive(Debug)]
+pub struct OnHint {
+    pub target: PointyHexGrid,
+    pub is_mine: bool,
+}
+
+/// Shows or hides the mine probability of each closed tile.
+#[derive(Debug)]
+pub struct OnToggleHeatmap;
+
+/// Starts a freshly generated board of the chosen difficulty.
+#[derive(Debug)]
+pub struct OnSelectDifficulty {
+    pub shape: BoardShape,
+    pub mine_count: MineCount,
+    /// Generate a board that can be cleared without guessing.
+    pub is_no_guess: bool,
+    pub rule: GameRule,
+}
 
 pub struct EventsPlugin;
 
@@ -46,21 +158,45 @@ impl Plugin for EventsPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<OnTryOpenTile>()
             .add_event::<OnTryFlagTile>()
+            .add_event::<OnTryChordTile>()
             .add_event::<OnMoveTile>()
             .add_event::<OnGameOver>()
             .add_event::<OnGameClear>()
+            .add_event::<OnRescue>()
             .add_event::<OnQuitGame>()
             .add_event::<OnRetry>()
+            .add_event::<OnResetBoard>()
+            .add_event::<OnSelectLevel>()
+            .add_event::<OnStartReplay>()
+            .add_event::<OnStopReplay>()
             .add_event::<OnTryUndo>()
+            .add_event::<OnTryRedo>()
+            .add_event::<OnRedoTile>()
             .add_event::<OnUndoTile>()
+            .add_event::<OnTryHint>()
+            .add_event::<OnHint>()
+            .add_event::<OnToggleHeatmap>()
+            .add_event::<OnSelectDifficulty>()
             .add_system(info_on_try_open_tile_system)
             .add_system(info_on_move_tile_system)
             .add_system(info_on_try_flag_tile_system)
+            .add_system(info_on_try_chord_tile_system)
             .add_system(info_on_game_over_system)
             .add_system(info_on_game_clear_system)
+            .add_system(info_on_rescue_system)
             .add_system(info_on_retry_system)
+            .add_system(info_on_reset_board_system)
+            .add_system(info_on_select_level_system)
+            .add_system(info_on_start_replay_system)
+            .add_system(info_on_stop_replay_system)
             .add_system(info_on_try_undo_system)
-            .add_system(info_on_undo_tile_system);
+            .add_system(info_on_try_redo_system)
+            .add_system(info_on_undo_tile_system)
+            .add_system(info_on_redo_tile_system)
+            .add_system(info_on_try_hint_system)
+            .add_system(info_on_hint_system)
+            .add_system(info_on_toggle_heatmap_system)
+            .add_system(info_on_select_difficulty_system);
     }
 }
 
@@ -82,6 +218,12 @@ fn info_on_try_flag_tile_system(mut reader: EventReader<OnTryFlagTile>) {
     }
 }
 
+fn info_on_try_chord_tile_system(mut reader: EventReader<OnTryChordTile>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
 fn info_on_game_over_system(mut reader: EventReader<OnGameOver>) {
     for event in reader.iter() {
         info!("{:?}", event);
@@ -94,20 +236,86 @@ fn info_on_game_clear_system(mut reader: EventReader<OnGameClear>) {
     }
 }
 
+fn info_on_rescue_system(mut reader: EventReader<OnRescue>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
 fn info_on_retry_system(mut reader: EventReader<OnRetry>) {
     for event in reader.iter() {
         info!("{:?}", event);
     }
 }
 
+fn info_on_reset_board_system(mut reader: EventReader<OnResetBoard>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_select_level_system(mut reader: EventReader<OnSelectLevel>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_start_replay_system(mut reader: EventReader<OnStartReplay>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_stop_replay_system(mut reader: EventReader<OnStopReplay>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
 fn info_on_try_undo_system(mut reader: EventReader<OnTryUndo>) {
     for event in reader.iter() {
         info!("{:?}", event);
     }
 }
 
+fn info_on_try_redo_system(mut reader: EventReader<OnTryRedo>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
 fn info_on_undo_tile_system(mut reader: EventReader<OnUndoTile>) {
     for event in reader.iter() {
         info!("{:?}", event);
     }
 }
+
+fn info_on_redo_tile_system(mut reader: EventReader<OnRedoTile>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_try_hint_system(mut reader: EventReader<OnTryHint>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_hint_system(mut reader: EventReader<OnHint>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_toggle_heatmap_system(mut reader: EventReader<OnToggleHeatmap>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
+
+fn info_on_select_difficulty_system(mut reader: EventReader<OnSelectDifficulty>) {
+    for event in reader.iter() {
+        info!("{:?}", event);
+    }
+}
@@ -1,12 +1,21 @@
 use bevy::prelude::Vec2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PointyHexGrid {
     pub x: i32,
     pub y: i32,
 }
 
 impl PointyHexGrid {
+    /// The six adjacent grids, counter-clockwise from the right.
+    pub fn neighbors(&self) -> [PointyHexGrid; 6] {
+        [[1, 0], [0, 1], [-1, 1], [-1, 0], [0, -1], [1, -1]].map(|[dx, dy]| PointyHexGrid {
+            x: self.x + dx,
+            y: self.y + dy,
+        })
+    }
+
     pub fn pointy_hex_grid_to_cartesian(&self) -> Vec2 {
         Vec2 {
             x: 3.0_f32.sqrt() * 0.5 * (2 * self.x + self.y) as f32,
@@ -64,3 +73,86 @@ pub fn pointy_hex_point_to_nearest_pointy_hex_grid(pointy_hex_point: Vec2) -> Po
         y: r_round as i32,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn distance(a: PointyHexGrid, b: PointyHexGrid) -> i32 {
+        let (dx, dy) = (a.x - b.x, a.y - b.y);
+        (dx.abs() + dy.abs() + (dx + dy).abs()) / 2
+    }
+
+    fn grid_strategy() -> impl Strategy<Value = PointyHexGrid> {
+        (-1000..1000, -1000..1000).prop_map(|(x, y)| PointyHexGrid { x, y })
+    }
+
+    #[test]
+    fn test_neighbors_are_distinct_and_adjacent() {
+        let origin = PointyHexGrid { x: 3, y: -2 };
+        let neighbors = origin.neighbors();
+        for (i, neighbor) in neighbors.iter().enumerate() {
+            assert_eq!(distance(origin, *neighbor), 1);
+            assert!(!neighbors[..i].contains(neighbor));
+        }
+    }
+
+    #[test]
+    fn test_neighbors_are_one_tile_apart_on_screen() {
+        let origin = PointyHexGrid::default();
+        for neighbor in origin.neighbors() {
+            let gap = pointy_hex_grid_to_cartesian(neighbor) - pointy_hex_grid_to_cartesian(origin);
+            assert!((gap.length() - 3.0_f32.sqrt()).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_nearest_grid_rounds_across_borders() {
+        // 頂点 (0, 1) は 3 つのタイルの境目なので，少しずらしてどちらに寄るか確かめる
+        let top = Vec2 { x: 0.0, y: 0.9 };
+        assert_eq!(
+            cartesian_point_to_nearest_pointy_hex_grid(top),
+            PointyHexGrid { x: 0, y: 0 }
+        );
+        let above_top = Vec2 { x: 0.0, y: 1.1 };
+        assert_ne!(
+            cartesian_point_to_nearest_pointy_hex_grid(above_top),
+            PointyHexGrid { x: 0, y: 0 }
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_grid_round_trips_through_cartesian(grid in grid_strategy()) {
+            let point = pointy_hex_grid_to_cartesian(grid);
+            prop_assert_eq!(point, grid.pointy_hex_grid_to_cartesian());
+            prop_assert_eq!(cartesian_point_to_nearest_pointy_hex_grid(point), grid);
+        }
+
+        #[test]
+        fn prop_points_inside_a_tile_snap_to_it(
+            grid in grid_strategy(),
+            angle in 0.0_f32..std::f32::consts::TAU,
+            // 内接円の半径は sqrt(3) / 2
+            radius in 0.0_f32..0.85,
+        ) {
+            let point = pointy_hex_grid_to_cartesian(grid) + Vec2::from_angle(angle) * radius;
+            prop_assert_eq!(cartesian_point_to_nearest_pointy_hex_grid(point), grid);
+        }
+
+        #[test]
+        fn prop_hex_and_cartesian_are_inverse(x in -100.0_f32..100.0, y in -100.0_f32..100.0) {
+            let point = Vec2 { x, y };
+            let round_trip = pointy_hex_to_cartesian(cartesian_to_pointy_hex(point));
+            prop_assert!((round_trip - point).length() < 1e-3);
+        }
+
+        #[test]
+        fn prop_neighbors_are_symmetric(grid in grid_strategy()) {
+            for neighbor in grid.neighbors() {
+                prop_assert!(neighbor.neighbors().contains(&grid));
+            }
+        }
+    }
+}
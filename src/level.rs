@@ -0,0 +1,141 @@
+use crate::events::*;
+use crate::model::{BoardShape, BoardSource, GameBoard, BOARDS_DIR};
+use bevy::prelude::*;
+use std::fs;
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        let mut levels = Levels::load();
+        if let Some(BoardSource::Fixed(name)) = app.world.get_resource::<BoardSource>() {
+            levels.current = levels.boards.iter().position(|board| &board.name == name);
+            levels.cursor = levels.current.unwrap_or_default();
+        }
+        app.insert_resource(levels)
+            .add_system(on_select_level_system);
+    }
+}
+
+/// Summary of a board file shown in the level select.
+#[derive(Debug, Clone)]
+pub struct BoardInfo {
+    pub name: String,
+    pub title: Option<String>,
+    pub difficulty: Option<String>,
+    pub shape: BoardShape,
+    pub mine_count: usize,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct Levels {
+    pub boards: Vec<BoardInfo>,
+    /// Index of the board being played, if it is one of `boards`.
+    pub current: Option<usize>,
+    /// Index highlighted in the level select.
+    pub cursor: usize,
+}
+
+impl Levels {
+    /// Lists every board in `BOARDS_DIR`, numbered boards first in numeric order.
+    pub fn load() -> Self {
+        let mut boards = fs::read_dir(BOARDS_DIR)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+            .filter_map(|name| match GameBoard::load(&name) {
+                Ok(game_board) => Some(BoardInfo {
+                    name,
+                    title: game_board.metadata().title.clone(),
+                    difficulty: game_board.metadata().difficulty.clone(),
+                    shape: game_board.shape().clone(),
+                    mine_count: game_board.count_mines(),
+                }),
+                Err(err) => {
+                    warn!("skipping board {}: {}", name, err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        boards.sort_by_key(|board| {
+            (
+                board.name.parse::<usize>().unwrap_or(usize::MAX),
+                board.name.clone(),
+            )
+        });
+
+        Self {
+            boards,
+            ..Default::default()
+        }
+    }
+
+    pub fn next(&self) -> Option<usize> {
+        self.current
+            .map(|current| current + 1)
+            .filter(|next| *next < self.boards.len())
+    }
+}
+
+fn on_select_level_system(
+    mut reader: EventReader<OnSelectLevel>,
+    mut levels: ResMut<Levels>,
+    mut game_board: ResMut<GameBoard>,
+    mut board_source: ResMut<BoardSource>,
+    mut writer: EventWriter<OnResetBoard>,
+) {
+    for event in reader.iter() {
+        let Some(board) = levels.boards.get(event.index) else {
+            continue;
+        };
+        match GameBoard::load(&board.name) {
+            Ok(new_board) => {
+                *board_source = BoardSource::Fixed(board.name.clone());
+                *game_board = new_board;
+                levels.current = Some(event.index);
+                levels.cursor = event.index;
+                writer.send(OnResetBoard);
+            }
+            Err(err) => error!("failed to load board {}: {}", board.name, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_are_sorted_numbered_first() {
+        let levels = Levels::load();
+        let names = levels
+            .boards
+            .iter()
+            .map(|board| board.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["1", "2", "3", "empty"]);
+        assert_eq!(levels.boards[0].shape, BoardShape::hexagon(4));
+        assert_eq!(levels.boards[1].title.as_deref(), Some("Flags first"));
+        assert_eq!(
+            levels.boards[2].shape,
+            BoardShape::Ring {
+                tiles_per_side: 4,
+                hole_tiles_per_side: 2
+            }
+        );
+        assert_eq!(levels.boards[3].mine_count, 0);
+    }
+
+    #[test]
+    fn test_next_level() {
+        let mut levels = Levels::load();
+        assert_eq!(levels.next(), None);
+        levels.current = Some(0);
+        assert_eq!(levels.next(), Some(1));
+        levels.current = Some(3);
+        assert_eq!(levels.next(), None);
+    }
+}
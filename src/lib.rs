@@ -0,0 +1,8 @@
+//! Rules of hex minesweeper. `model::Game` plays a game without a window;
+//! the plugins in `model` and `events` drive the same rules inside a Bevy app.
+
+pub mod events;
+pub mod hexgrid;
+pub mod model;
+pub mod solver;
+pub mod strategy;
@@ -1,22 +1,32 @@
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::render::camera::RenderTarget;
-pub use std::io::*;
+
+use hex_minesweeper::{events, hexgrid, model, solver};
 
 mod controller;
-mod events;
-mod hexgrid;
-mod model;
-mod read_macro;
+mod level;
+mod preset;
+mod replay;
+mod save;
 mod scene;
+mod stats;
 mod view;
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins.set(LogPlugin {
-            filter: "info,wgpu_core=warn,wgpu_hal=warn,mygame=debug".into(),
-            level: bevy::log::Level::DEBUG,
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(LogPlugin {
+                    filter: "info,wgpu_core=warn,wgpu_hal=warn,mygame=debug".into(),
+                    level: bevy::log::Level::DEBUG,
+                })
+                .set(WindowPlugin {
+                    // 閉じる前にセーブするため，ウィンドウは OnQuitGame の後で閉じる
+                    close_when_requested: false,
+                    ..default()
+                }),
+        )
         .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
         .insert_resource(Config {
             tile_size: 50.0,
@@ -33,9 +43,15 @@ fn main() {
             tile_color: Color::rgb(0.1, 0.1, 0.1),
             tile_edge_color: Color::rgb(0.8, 0.8, 0.8),
             tile_selected_color: Color::rgb(0.4, 0.4, 0.4),
+            tile_hint_safe_color: Color::rgb(0.1, 0.4, 0.2),
+            tile_hint_mine_color: Color::rgb(0.5, 0.1, 0.1),
+            tile_heatmap_color: Color::rgb(0.7, 0.3, 0.0),
             tile_text_hint_color: Color::rgb(0.9, 0.9, 0.9),
             tile_text_flag_color: Color::rgb(0.8, 0.8, 0.0),
+            tile_text_question_color: Color::rgb(0.3, 0.7, 0.9),
+            tile_text_wrong_flag_color: Color::rgb(0.9, 0.4, 0.0),
             tile_text_mine_color: Color::rgb(0.8, 0.0, 0.0),
+            tile_text_defused_color: Color::rgb(0.0, 0.7, 0.3),
             game_over_background_color: Color::rgba(0.0, 0.0, 0.0, 0.9),
             game_over_text_color: Color::rgb(0.9, 0.9, 0.9),
             game_over_text_position: Vec2 { x: 0.0, y: 20.0 },
@@ -45,7 +61,7 @@ fn main() {
             game_over_text_below_color: Color::rgb(0.9, 0.9, 0.9),
             game_over_text_below_position: Vec2 { x: 0.0, y: -60.0 },
             game_over_text_below_size: 40.0,
-            game_over_text_below: "click anywhere to retry".to_string(),
+            game_over_text_below: "left click: retry / right click: new board".to_string(),
             game_clear_background_color: Color::rgba(0.0, 0.0, 0.0, 0.9),
             game_clear_text_color: Color::rgb(0.9, 0.9, 0.9),
             game_clear_text_position: Vec2 { x: 0.0, y: 20.0 },
@@ -54,7 +70,8 @@ fn main() {
             game_clear_text_below_color: Color::rgb(0.9, 0.9, 0.9),
             game_clear_text_below_position: Vec2 { x: 0.0, y: -60.0 },
             game_clear_text_below_size: 40.0,
-            game_clear_text_below: "click anywhere to retry".to_string(),
+            game_clear_text_below: "left click: retry / right click: new board / N: next level"
+                .to_string(),
             tile_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
             game_over_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
             game_clear_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
@@ -69,17 +86,79 @@ fn main() {
             mines_hint_text_color: Color::rgb(0.9, 0.9, 0.9),
             mines_hint_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
             mines_hint_text_size: 40.0,
+            stats_text_position: Vec2 {
+                x: -620.0,
+                y: 300.0,
+            },
+            stats_text_color: Color::rgb(0.9, 0.9, 0.9),
+            stats_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
+            stats_text_size: 30.0,
+            stats_text_layer: 0.9,
+            game_clear_stats_text_position: Vec2 { x: 0.0, y: -120.0 },
+            game_clear_stats_text_size: 30.0,
+            level_select_background_color: Color::rgba(0.0, 0.0, 0.0, 0.9),
+            level_select_background_layer: 1.0,
+            level_select_title: "Select Level (up/down, enter, esc)".to_string(),
+            level_select_text_color: Color::rgb(0.9, 0.9, 0.9),
+            level_select_text_cursor_color: Color::rgb(0.8, 0.8, 0.0),
+            level_select_text_position: Vec2 { x: 0.0, y: 250.0 },
+            level_select_text_size: 40.0,
+            level_select_text_layer: 1.1,
+            level_select_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
+            difficulty_menu_background_color: Color::rgba(0.0, 0.0, 0.0, 0.9),
+            difficulty_menu_background_layer: 1.0,
+            difficulty_menu_title: "Difficulty (up/down, enter, esc)".to_string(),
+            difficulty_menu_custom_controls:
+                "up/down: field / left/right: change / enter: start / esc: back".to_string(),
+            difficulty_menu_text_color: Color::rgb(0.9, 0.9, 0.9),
+            difficulty_menu_text_cursor_color: Color::rgb(0.8, 0.8, 0.0),
+            difficulty_menu_text_error_color: Color::rgb(0.9, 0.4, 0.0),
+            difficulty_menu_text_position: Vec2 { x: 0.0, y: 250.0 },
+            difficulty_menu_text_size: 40.0,
+            difficulty_menu_text_layer: 1.1,
+            difficulty_menu_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
+            tilemap_screen_ratio: Vec2 { x: 0.9, y: 0.8 },
+            ghost_cursor_color: Color::rgba(0.3, 0.7, 0.9, 0.6),
+            ghost_cursor_size: 12.0,
+            ghost_cursor_layer: 0.8,
+            replay_text_color: Color::rgb(0.3, 0.7, 0.9),
+            replay_text_position: Vec2 {
+                x: -620.0,
+                y: -320.0,
+            },
+            replay_text_size: 30.0,
+            replay_text_layer: 0.9,
+            replay_text_font_path: "fonts/FiraSans-Bold.ttf".to_string(),
+            replay_text_controls: "space: pause / up, down: speed / esc: stop".to_string(),
+        })
+        .insert_resource(model::BoardSource::Generate {
+            shape: model::BoardShape::hexagon(4),
+            mine_count: model::MineCount::Density(0.2),
+            seed: None,
+            first_click: model::FirstClickSafety::Opening,
+        })
+        .insert_resource(model::GameRule {
+            practice: false,
+            lives: 1,
+            strict_flag: true,
+            win_rule: model::WinRule::Either,
         })
         .insert_resource(CursorWorldPosition {
             position: Vec2::ZERO,
         })
         .add_startup_system(setup)
         .add_system_to_stage(CoreStage::PreUpdate, update_cursor_world_position_system)
+        .add_plugin(save::SavePlugin)
         .add_plugin(model::ModelPlugin)
+        .add_plugin(solver::SolverPlugin)
+        .add_plugin(level::LevelPlugin)
+        .add_plugin(preset::PresetPlugin)
         .add_plugin(view::ViewPlugin)
         .add_plugin(controller::ControllerPlugin)
         .add_plugin(events::EventsPlugin)
         .add_plugin(scene::ScenePlugin)
+        .add_plugin(stats::StatsPlugin)
+        .add_plugin(replay::ReplayPlugin)
         .run();
 }
 
@@ -100,9 +179,15 @@ pub struct Config {
     pub tile_color: Color,
     pub tile_edge_color: Color,
     pub tile_selected_color: Color,
+    pub tile_hint_safe_color: Color,
+    pub tile_hint_mine_color: Color,
+    pub tile_heatmap_color: Color,
     pub tile_text_hint_color: Color,
     pub tile_text_flag_color: Color,
+    pub tile_text_question_color: Color,
+    pub tile_text_wrong_flag_color: Color,
     pub tile_text_mine_color: Color,
+    pub tile_text_defused_color: Color,
     pub game_over_background_color: Color,
     pub game_over_text_color: Color,
     pub game_clear_background_color: Color,
@@ -140,6 +225,48 @@ pub struct Config {
     pub mines_hint_text_size: f32,
     pub mines_hint_text_font_path: String,
     pub mines_hint_text: String,
+
+    pub stats_text_color: Color,
+    pub stats_text_position: Vec2,
+    pub stats_text_size: f32,
+    pub stats_text_font_path: String,
+    pub stats_text_layer: f32,
+    pub game_clear_stats_text_position: Vec2,
+    pub game_clear_stats_text_size: f32,
+
+    pub level_select_background_color: Color,
+    pub level_select_background_layer: f32,
+    pub level_select_title: String,
+    pub level_select_text_color: Color,
+    pub level_select_text_cursor_color: Color,
+    pub level_select_text_position: Vec2,
+    pub level_select_text_size: f32,
+    pub level_select_text_layer: f32,
+    pub level_select_text_font_path: String,
+
+    pub difficulty_menu_background_color: Color,
+    pub difficulty_menu_background_layer: f32,
+    pub difficulty_menu_title: String,
+    pub difficulty_menu_custom_controls: String,
+    pub difficulty_menu_text_color: Color,
+    pub difficulty_menu_text_cursor_color: Color,
+    pub difficulty_menu_text_error_color: Color,
+    pub difficulty_menu_text_position: Vec2,
+    pub difficulty_menu_text_size: f32,
+    pub difficulty_menu_text_layer: f32,
+    pub difficulty_menu_text_font_path: String,
+
+    pub tilemap_screen_ratio: Vec2,
+
+    pub ghost_cursor_color: Color,
+    pub ghost_cursor_size: f32,
+    pub ghost_cursor_layer: f32,
+    pub replay_text_color: Color,
+    pub replay_text_position: Vec2,
+    pub replay_text_size: f32,
+    pub replay_text_layer: f32,
+    pub replay_text_font_path: String,
+    pub replay_text_controls: String,
 }
 
 fn setup(mut commands: Commands, mut windows: ResMut<Windows>) {
@@ -169,7 +296,7 @@ fn update_cursor_world_position_system(
     };
 
     if let Some(screen_pos) = window.cursor_position() {
-        let window_size = Vec2::new(window.width() as f32, window.height() as f32);
+        let window_size = Vec2::new(window.width(), window.height());
 
         let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
 
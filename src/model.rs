@@ -2,32 +2,226 @@ use crate::events;
 use crate::events::OnMoveTile;
 use crate::hexgrid;
 use crate::hexgrid::PointyHexGrid;
-use crate::read_to_end;
 use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fs;
-use std::io::*;
+
+mod board_file;
+mod game;
+mod no_guess;
+mod shape;
+
+pub use board_file::{BoardMetadata, BoardParseError};
+pub use game::{Game, Outcome, UndoOutcome};
+pub use no_guess::{GenerationReport, NO_GUESS_MAX_ATTEMPTS, NO_GUESS_TIME_LIMIT};
+pub use shape::BoardShape;
+
+pub const BOARDS_DIR: &str = "assets/boards";
 
 pub struct ModelPlugin;
 
 impl Plugin for ModelPlugin {
     fn build(&self, app: &mut App) {
-        if let Ok(game_board) = GameBoard::load(1) {
+        // 再開したゲームは盤面がすでにある
+        if !app.world.contains_resource::<GameBoard>() {
+            let mut board_source = app
+                .world
+                .get_resource::<BoardSource>()
+                .cloned()
+                .unwrap_or_default();
+            let game_board = board_source.build().unwrap_or_else(|err| {
+                error!("failed to load game board, generating one instead: {}", err);
+                board_source = BoardSource::default();
+                board_source
+                    .build()
+                    .expect("generated boards are always valid")
+            });
             app.insert_resource(game_board)
-                .add_system(on_try_open_tile_system)
-                .add_system(on_try_flag_tile_system)
-                .add_system(on_undo_system);
-        } else {
-            debug_assert!(true, "failed to load game board");
+                .insert_resource(board_source);
+        }
+        if let Some(seed) = app.world.resource::<GameBoard>().seed() {
+            info!("board seed: {}", seed);
+        }
+        app.init_resource::<GameRule>()
+            .add_system(on_try_open_tile_system)
+            .add_system(on_try_flag_tile_system)
+            .add_system(on_try_chord_tile_system)
+            .add_system(on_undo_system)
+            .add_system(on_redo_system)
+            .add_system(on_retry_system);
+    }
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct GameRule {
+    /// Allows undoing the move that ended the game.
+    pub practice: bool,
+    /// Opening a mine costs a life, and only the last one ends the game.
+    pub lives: usize,
+    /// Flagging a safe tile ends the game. Otherwise wrong flags are only shown when the game ends.
+    pub strict_flag: bool,
+    pub win_rule: WinRule,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WinRule {
+    /// Every safe tile is open (classic minesweeper).
+    SafeTilesOpened,
+    /// Every mine is flagged and no safe tile is.
+    MinesFlagged,
+    /// Whichever of the two comes first.
+    #[default]
+    Either,
+}
+
+impl WinRule {
+    pub const ALL: [WinRule; 3] = [Self::SafeTilesOpened, Self::MinesFlagged, Self::Either];
+
+    /// Name used in board files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SafeTilesOpened => "safe_tiles_opened",
+            Self::MinesFlagged => "mines_flagged",
+            Self::Either => "either",
+        }
+    }
+
+    /// The rule after this one in `ALL`, wrapping around.
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|rule| rule == self);
+        Self::ALL[(index.unwrap_or_default() + 1) % Self::ALL.len()]
+    }
+}
+
+/// Where the `GameBoard` comes from when the game starts.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub enum BoardSource {
+    /// Hand-authored board `assets/boards/{name}.txt`.
+    Fixed(String),
+    /// Randomly generated board. Without a seed a random one is picked.
+    Generate {
+        shape: BoardShape,
+        mine_count: MineCount,
+        seed: Option<String>,
+        first_click: FirstClickSafety,
+    },
+}
+
+impl Default for GameRule {
+    fn default() -> Self {
+        Self {
+            practice: false,
+            lives: 1,
+            strict_flag: true,
+            win_rule: default(),
         }
     }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+impl Default for BoardSource {
+    fn default() -> Self {
+        Self::Generate {
+            shape: BoardShape::hexagon(4),
+            mine_count: MineCount::Density(0.2),
+            seed: None,
+            first_click: FirstClickSafety::Opening,
+        }
+    }
+}
+
+impl BoardSource {
+    pub fn build(&self) -> Result<GameBoard, BoardParseError> {
+        match self {
+            Self::Fixed(name) => GameBoard::load(name),
+            Self::Generate {
+                shape,
+                mine_count,
+                seed,
+                first_click,
+            } => {
+                let seed = seed
+                    .clone()
+                    .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+                Ok(GameBoard::generate(
+                    shape.clone(),
+                    *mine_count,
+                    &seed,
+                    *first_click,
+                ))
+            }
+        }
+    }
+
+    /// Like `build`, but a generated board always gets a new random seed.
+    pub fn build_fresh(&self) -> Result<GameBoard, BoardParseError> {
+        match self {
+            Self::Fixed(_) => self.build(),
+            Self::Generate {
+                shape,
+                mine_count,
+                first_click,
+                ..
+            } => Self::Generate {
+                shape: shape.clone(),
+                mine_count: *mine_count,
+                seed: None,
+                first_click: *first_click,
+            }
+            .build(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum MineCount {
+    Exact(usize),
+    /// Ratio of mines to tiles, in `0.0..=1.0`.
+    Density(f32),
+}
+
+impl MineCount {
+    pub fn resolve(&self, tile_count: usize) -> usize {
+        match *self {
+            Self::Exact(count) => count.min(tile_count),
+            Self::Density(density) => {
+                ((tile_count as f32 * density.clamp(0.0, 1.0)).round() as usize).min(tile_count)
+            }
+        }
+    }
+}
+
+/// Which tiles a generated board keeps free of mines on the first open.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FirstClickSafety {
+    /// Mines are placed up front, so the first open may hit one.
+    #[default]
+    None,
+    /// Only the first opened tile is safe.
+    Tile,
+    /// The first opened tile and its six neighbours are safe.
+    Opening,
+    /// Like `Opening`, and the rest of the board can be cleared by deduction alone.
+    NoGuess,
+}
+
+/// Mines of a generated board that are placed on the first open.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct PendingMines {
+    mine_count: usize,
+    first_click: FirstClickSafety,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TileState {
     is_open: bool,
     is_flag: bool,
+    is_question: bool,
     is_mine: bool,
+    is_defused: bool,
 }
 
 impl TileState {
@@ -39,105 +233,431 @@ impl TileState {
         self.is_flag
     }
 
+    pub fn is_question(&self) -> bool {
+        self.is_question
+    }
+
     pub fn is_mine(&self) -> bool {
         self.is_mine
     }
+
+    /// An opened mine that cost a life instead of ending the game.
+    pub fn is_defused(&self) -> bool {
+        self.is_open && self.is_mine && self.is_defused
+    }
+
+    pub fn is_exploded(&self) -> bool {
+        self.is_open && self.is_mine && !self.is_defused
+    }
 }
 
-#[derive(Resource, Default, Debug)]
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedGameBoard")]
 pub struct GameBoard {
-    tiles_per_side: usize,
+    shape: BoardShape,
     board: Vec<TileState>,
     move_stack: VecDeque<OnMoveTile>,
+    redo_stack: Vec<OnMoveTile>,
+    seed: Option<String>,
+    pending_mines: Option<PendingMines>,
+    is_game_over: bool,
+    rescue_count: usize,
+    metadata: BoardMetadata,
+    #[serde(default)]
+    generation: Option<GenerationReport>,
+}
+
+// 読み込んだタイルの数が形と合わないと，後で添字が範囲外になる
+#[derive(Deserialize)]
+struct UncheckedGameBoard {
+    shape: BoardShape,
+    board: Vec<TileState>,
+    move_stack: VecDeque<OnMoveTile>,
+    redo_stack: Vec<OnMoveTile>,
+    seed: Option<String>,
+    pending_mines: Option<PendingMines>,
+    is_game_over: bool,
+    rescue_count: usize,
+    metadata: BoardMetadata,
+    #[serde(default)]
+    generation: Option<GenerationReport>,
+}
+
+impl TryFrom<UncheckedGameBoard> for GameBoard {
+    type Error = String;
+
+    fn try_from(unchecked: UncheckedGameBoard) -> Result<Self, Self::Error> {
+        let (width, height) = unchecked.shape.size();
+        if unchecked.board.len() != width * height {
+            return Err(format!(
+                "{} tiles do not fit a {}x{} board",
+                unchecked.board.len(),
+                width,
+                height
+            ));
+        }
+        Ok(Self {
+            shape: unchecked.shape,
+            board: unchecked.board,
+            move_stack: unchecked.move_stack,
+            redo_stack: unchecked.redo_stack,
+            seed: unchecked.seed,
+            pending_mines: unchecked.pending_mines,
+            is_game_over: unchecked.is_game_over,
+            rescue_count: unchecked.rescue_count,
+            metadata: unchecked.metadata,
+            generation: unchecked.generation,
+        })
+    }
 }
 
 impl GameBoard {
+    /// An empty hexagonal board with `tiles_per_side` tiles along each edge.
     pub fn new(tiles_per_side: usize) -> Self {
+        Self::from_shape(BoardShape::hexagon(tiles_per_side))
+    }
+
+    /// An empty board of any shape.
+    pub fn from_shape(shape: BoardShape) -> Self {
+        let (width, height) = shape.size();
         Self {
-            tiles_per_side,
-            board: vec![default(); (2 * tiles_per_side - 1) * (2 * tiles_per_side - 1)],
+            shape,
+            board: vec![default(); width * height],
             ..Default::default()
         }
     }
 
-    pub fn load(id: usize) -> Result<Self> {
-        use crate::*;
-        let stdin = fs::File::open(format!("assets/boards/{}.txt", id))?;
-        let mut reader = BufReader::new(stdin);
-        read_to_end!(
-            reader,
-            tiles_per_side: usize,
-            board_text: [chars; 2 * tiles_per_side - 1]
-        );
+    /// Builds a board with mines placed at random. The same `seed` always yields the same board.
+    ///
+    /// Unless `first_click` is `FirstClickSafety::None`, mines are placed by
+    /// `place_pending_mines` on the first open instead, and the board then
+    /// depends on the seed and the first opened tile.
+    pub fn generate(
+        shape: BoardShape,
+        mine_count: MineCount,
+        seed: &str,
+        first_click: FirstClickSafety,
+    ) -> Self {
+        let mut board = Self::from_shape(shape);
+        let mine_count = mine_count.resolve(board.grids().count());
+        board.seed = Some(seed.to_string());
 
-        let mut board = Self {
-            tiles_per_side,
-            board: vec![default(); (2 * tiles_per_side - 1) * (2 * tiles_per_side - 1)],
-            ..Default::default()
+        if first_click == FirstClickSafety::None {
+            board.place_mines(mine_count, &[], 0);
+        } else {
+            board.pending_mines = Some(PendingMines {
+                mine_count,
+                first_click,
+            });
+        }
+        board
+    }
+
+    /// Places the mines of a lazily generated board so that `first_open` is safe.
+    /// Does nothing if the mines are already placed.
+    pub fn place_pending_mines(&mut self, first_open: PointyHexGrid) {
+        if let Some(PendingMines {
+            mine_count,
+            first_click,
+        }) = self.pending_mines.take()
+        {
+            let mut safe_grids = vec![first_open];
+            if let FirstClickSafety::Opening | FirstClickSafety::NoGuess = first_click {
+                safe_grids.extend(self.adjacent_grids(first_open));
+            }
+            // 盤面が小さすぎる場合は開けたタイルだけを安全にする
+            if self.grids().count() < mine_count + safe_grids.len() {
+                safe_grids.truncate(1);
+            }
+            if first_click == FirstClickSafety::NoGuess {
+                self.place_no_guess_mines(mine_count, &safe_grids);
+            } else {
+                self.place_mines(mine_count, &safe_grids, 0);
+            }
+        }
+    }
+
+    pub fn is_mines_pending(&self) -> bool {
+        self.pending_mines.is_some()
+    }
+
+    /// Places mines off `safe_grids`. Each `attempt` gives another layout for the same seed.
+    fn place_mines(&mut self, mine_count: usize, safe_grids: &[PointyHexGrid], attempt: u64) {
+        let mut grids = self
+            .grids()
+            .filter(|grid| !safe_grids.contains(grid))
+            .collect::<Vec<_>>();
+
+        let seed = self.seed.as_deref().unwrap_or_default();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed_to_u64(seed).wrapping_add(attempt));
+        grids.shuffle(&mut rng);
+        for grid in grids.into_iter().take(mine_count) {
+            if let Some(tile_state) = self.get_mut(grid) {
+                tile_state.is_mine = true;
+            }
+        }
+    }
+
+    pub fn load(name: &str) -> Result<Self, BoardParseError> {
+        let text = fs::read_to_string(format!("{}/{}.txt", BOARDS_DIR, name))?;
+        board_file::parse(&text)
+    }
+
+    /// Parses a board written in the board file format.
+    pub fn from_board_text(text: &str) -> Result<Self, BoardParseError> {
+        board_file::parse(text)
+    }
+
+    /// Serializes the board in the latest board file format.
+    pub fn to_board_text(&self) -> String {
+        board_file::write(self)
+    }
+
+    /// Records a new move. Any undone moves can no longer be redone.
+    pub fn push_move(&mut self, move_tile: OnMoveTile) {
+        self.move_stack.push_back(move_tile);
+        self.redo_stack.clear();
+    }
+
+    /// Reverts the last move on the move stack and returns the matching event.
+    pub fn undo(&mut self) -> Option<events::OnUndoTile> {
+        let prev_move = self.move_stack.pop_back()?;
+        self.redo_stack.push(prev_move.clone());
+        let undo_event = match prev_move {
+            OnMoveTile::Open { target } => {
+                if let Some(tile_state) = self.get_mut(target) {
+                    tile_state.is_open = false;
+                }
+                events::OnUndoTile::UnOpen { target }
+            }
+            OnMoveTile::OpenRegion { targets } => {
+                for target in &targets {
+                    if let Some(tile_state) = self.get_mut(*target) {
+                        tile_state.is_open = false;
+                    }
+                }
+                events::OnUndoTile::UnOpenRegion { targets }
+            }
+            OnMoveTile::Flag { target } => {
+                if let Some(tile_state) = self.get_mut(target) {
+                    tile_state.is_flag = false;
+                }
+                events::OnUndoTile::UnFlag { target }
+            }
+            OnMoveTile::Question { target } => {
+                if let Some(tile_state) = self.get_mut(target) {
+                    tile_state.is_question = false;
+                    tile_state.is_flag = true;
+                }
+                events::OnUndoTile::UnQuestion { target }
+            }
+            OnMoveTile::ClearMark { target } => {
+                if let Some(tile_state) = self.get_mut(target) {
+                    tile_state.is_question = true;
+                }
+                events::OnUndoTile::UnClearMark { target }
+            }
         };
+        Some(undo_event)
+    }
 
-        for x in 0..(2 * tiles_per_side - 1) {
-            for y in 0..(2 * tiles_per_side - 1) {
-                if let Some(tile_state) = board.get_mut(PointyHexGrid {
-                    x: x as i32,
-                    y: y as i32,
-                }) {
-                    *tile_state = match board_text[y][x] {
-                        '.' => TileState {
-                            is_open: false,
-                            is_flag: false,
-                            is_mine: false,
-                        },
-                        'O' => TileState {
-                            is_open: true,
-                            is_flag: false,
-                            is_mine: false,
-                        },
-                        'M' => TileState {
-                            is_open: false,
-                            is_flag: false,
-                            is_mine: true,
-                        },
-                        _ => {
-                            panic!("board text error! : {}", board_text[y][x])
-                        }
+    /// Re-applies the last undone move and returns it.
+    pub fn redo(&mut self) -> Option<OnMoveTile> {
+        let next_move = self.redo_stack.pop()?;
+        match &next_move {
+            OnMoveTile::Open { target } => {
+                if let Some(tile_state) = self.get_mut(*target) {
+                    tile_state.is_open = true;
+                }
+            }
+            OnMoveTile::OpenRegion { targets } => {
+                for target in targets {
+                    if let Some(tile_state) = self.get_mut(*target) {
+                        tile_state.is_open = true;
                     }
                 }
             }
+            OnMoveTile::Flag { target } => {
+                if let Some(tile_state) = self.get_mut(*target) {
+                    tile_state.is_flag = true;
+                }
+            }
+            OnMoveTile::Question { target } => {
+                if let Some(tile_state) = self.get_mut(*target) {
+                    tile_state.is_flag = false;
+                    tile_state.is_question = true;
+                }
+            }
+            OnMoveTile::ClearMark { target } => {
+                if let Some(tile_state) = self.get_mut(*target) {
+                    tile_state.is_question = false;
+                }
+            }
         }
+        self.move_stack.push_back(next_move.clone());
+        Some(next_move)
+    }
+
+    /// Undoes every move, restoring the board to its original layout.
+    pub fn reset(&mut self) {
+        while self.undo().is_some() {}
+        self.redo_stack.clear();
+        self.is_game_over = false;
+        self.rescue_count = 0;
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.is_game_over
+    }
 
-        Ok(board)
+    /// How many times the losing move was undone in practice mode.
+    pub fn rescue_count(&self) -> usize {
+        self.rescue_count
     }
 
-    pub fn tiles_per_side(&self) -> usize {
-        self.tiles_per_side
+    pub fn shape(&self) -> &BoardShape {
+        &self.shape
+    }
+
+    pub fn seed(&self) -> Option<&str> {
+        self.seed.as_deref()
+    }
+
+    /// How the mines of a `FirstClickSafety::NoGuess` board were placed.
+    pub fn generation(&self) -> Option<&GenerationReport> {
+        self.generation.as_ref()
+    }
+
+    pub fn metadata(&self) -> &BoardMetadata {
+        &self.metadata
+    }
+
+    /// The board's own win rule if its file requires one, otherwise `default`.
+    pub fn win_rule(&self, default: WinRule) -> WinRule {
+        self.metadata.win_rule.unwrap_or(default)
+    }
+
+    /// Iterates over every in-bound tile of the board.
+    pub fn grids(&self) -> impl Iterator<Item = PointyHexGrid> + '_ {
+        self.shape.grids()
     }
 
     pub fn get(&self, grid: hexgrid::PointyHexGrid) -> Option<&TileState> {
+        let index = self.index(grid)?;
+        self.board.get(index)
+    }
+
+    pub fn get_mut(&mut self, grid: hexgrid::PointyHexGrid) -> Option<&mut TileState> {
+        let index = self.index(grid)?;
+        self.board.get_mut(index)
+    }
+
+    fn index(&self, grid: hexgrid::PointyHexGrid) -> Option<usize> {
         if self.is_out_of_bound(grid) {
             None
         } else {
-            self.board
-                .get((grid.y * (2 * self.tiles_per_side as i32 - 1) + grid.x) as usize)
+            Some(grid.y as usize * self.shape.size().0 + grid.x as usize)
         }
     }
 
-    pub fn get_mut(&mut self, grid: hexgrid::PointyHexGrid) -> Option<&mut TileState> {
-        if self.is_out_of_bound(grid) {
+    /// Defuses the mines among `opened` while lives remain and returns the mine
+    /// that exploded on the last life, if any.
+    pub fn defuse_opened_mines(
+        &mut self,
+        opened: &[PointyHexGrid],
+        lives: usize,
+    ) -> Option<PointyHexGrid> {
+        for grid in opened {
+            if !self.get(*grid).is_some_and(|t| t.is_exploded()) {
+                continue;
+            }
+            if self.count_lives_left(lives) <= 1 {
+                return Some(*grid);
+            }
+            if let Some(tile_state) = self.get_mut(*grid) {
+                tile_state.is_defused = true;
+            }
+        }
+        None
+    }
+
+    pub fn count_lives_left(&self, lives: usize) -> usize {
+        lives.max(1).saturating_sub(self.count_defused_mines())
+    }
+
+    /// Advances the marker of a closed tile: none → flag → question mark → none.
+    /// Returns the move that was made.
+    pub fn cycle_marker(&mut self, target: PointyHexGrid) -> Option<OnMoveTile> {
+        let tile_state = self.get_mut(target)?;
+        if tile_state.is_open {
             None
+        } else if tile_state.is_flag {
+            tile_state.is_flag = false;
+            tile_state.is_question = true;
+            Some(OnMoveTile::Question { target })
+        } else if tile_state.is_question {
+            tile_state.is_question = false;
+            Some(OnMoveTile::ClearMark { target })
         } else {
-            self.board
-                .get_mut((grid.y * (2 * self.tiles_per_side as i32 - 1) + grid.x) as usize)
+            tile_state.is_flag = true;
+            Some(OnMoveTile::Flag { target })
+        }
+    }
+
+    /// Opens `target` and, if it has no adjacent mines, cascades across the
+    /// connected zero region and its border. Returns the tiles that were opened.
+    pub fn open_region(&mut self, target: PointyHexGrid) -> Vec<PointyHexGrid> {
+        let mut opened = vec![];
+        let mut queue = VecDeque::from([target]);
+        while let Some(grid) = queue.pop_front() {
+            match self.get_mut(grid) {
+                Some(tile_state) if !tile_state.is_open && !tile_state.is_flag => {
+                    tile_state.is_open = true;
+                    tile_state.is_defused = false;
+                    opened.push(grid);
+                    if tile_state.is_mine {
+                        continue;
+                    }
+                }
+                _ => continue,
+            }
+            if self.count_adjacent_mines(grid) == Some(0) {
+                queue.extend(self.adjacent_grids(grid));
+            }
         }
+        opened
+    }
+
+    /// Opens every unflagged neighbour of an open hint tile whose adjacent flags
+    /// already match its number. Returns the tiles that were opened.
+    pub fn chord(&mut self, target: PointyHexGrid) -> Vec<PointyHexGrid> {
+        match self.get(target) {
+            Some(tile_state) if tile_state.is_open && !tile_state.is_mine => {}
+            _ => return vec![],
+        }
+        if self.count_adjacent_flags(target) != self.count_adjacent_mines(target) {
+            return vec![];
+        }
+
+        let mut opened = vec![];
+        for neighbor in target.neighbors() {
+            opened.extend(self.open_region(neighbor));
+        }
+        opened
     }
 
     pub fn is_out_of_bound(&self, grid: hexgrid::PointyHexGrid) -> bool {
-        grid.x < 0
-            || grid.x >= (2 * self.tiles_per_side - 1) as i32
-            || grid.y < 0
-            || grid.y >= (2 * self.tiles_per_side - 1) as i32
-            || grid.x + grid.y < (self.tiles_per_side - 1) as i32
-            || grid.x + grid.y > 3 * (self.tiles_per_side - 1) as i32
+        !self.shape.contains(grid)
+    }
+
+    /// Iterates over the in-bound tiles adjacent to `grid`.
+    pub fn adjacent_grids(
+        &self,
+        grid: hexgrid::PointyHexGrid,
+    ) -> impl Iterator<Item = PointyHexGrid> + '_ {
+        grid.neighbors()
+            .into_iter()
+            .filter(|neighbor| !self.is_out_of_bound(*neighbor))
     }
 
     pub fn count_adjacent_mines(&self, grid: hexgrid::PointyHexGrid) -> Option<usize> {
@@ -145,12 +665,8 @@ impl GameBoard {
             None
         } else {
             let mut count = 0;
-            let diff = [[1, 0], [0, 1], [-1, 1], [-1, 0], [0, -1], [1, -1]];
-            for [dx, dy] in diff {
-                if let Some(tile_state) = self.get(hexgrid::PointyHexGrid {
-                    x: grid.x + dx,
-                    y: grid.y + dy,
-                }) {
+            for neighbor in grid.neighbors() {
+                if let Some(tile_state) = self.get(neighbor) {
                     if tile_state.is_mine {
                         count += 1;
                     }
@@ -160,6 +676,18 @@ impl GameBoard {
         }
     }
 
+    pub fn count_adjacent_flags(&self, grid: hexgrid::PointyHexGrid) -> Option<usize> {
+        if self.is_out_of_bound(grid) {
+            None
+        } else {
+            Some(
+                self.adjacent_grids(grid)
+                    .filter(|neighbor| self.get(*neighbor).is_some_and(|t| t.is_flag))
+                    .count(),
+            )
+        }
+    }
+
     pub fn count_open_tile(&self) -> usize {
         self.board
             .iter()
@@ -168,6 +696,9 @@ impl GameBoard {
     }
 
     pub fn count_mines(&self) -> usize {
+        if let Some(pending_mines) = self.pending_mines {
+            return pending_mines.mine_count;
+        }
         self.board
             .iter()
             .filter(|tile_state| tile_state.is_mine)
@@ -181,94 +712,803 @@ impl GameBoard {
             .count()
     }
 
+    pub fn count_closed_safe_tiles(&self) -> usize {
+        self.grids()
+            .filter_map(|grid| self.get(grid))
+            .filter(|tile_state| !tile_state.is_mine && !tile_state.is_open)
+            .count()
+    }
+
+    pub fn count_flags(&self) -> usize {
+        self.board
+            .iter()
+            .filter(|tile_state| tile_state.is_flag)
+            .count()
+    }
+
+    pub fn count_wrong_flags(&self) -> usize {
+        self.board
+            .iter()
+            .filter(|tile_state| !tile_state.is_mine && tile_state.is_flag)
+            .count()
+    }
+
+    /// Whether the board satisfies `win_rule`. A board with an opened mine is never cleared.
+    pub fn is_cleared(&self, win_rule: WinRule) -> bool {
+        if self.is_mines_pending() || self.board.iter().any(|tile_state| tile_state.is_exploded()) {
+            return false;
+        }
+        let safe_tiles_opened = self.count_closed_safe_tiles() == 0;
+        let mines_flagged = self.count_remaining_mines() == 0 && self.count_wrong_flags() == 0;
+        match win_rule {
+            WinRule::SafeTilesOpened => safe_tiles_opened,
+            WinRule::MinesFlagged => mines_flagged,
+            WinRule::Either => safe_tiles_opened || mines_flagged,
+        }
+    }
+
+    /// Bechtel's Board Benchmark Value: the fewest opens needed to clear the
+    /// board without flags, i.e. one per zero region plus one per hint tile
+    /// that no zero region reveals.
+    pub fn count_3bv(&self) -> usize {
+        let is_zero = |grid: PointyHexGrid| {
+            self.get(grid).is_some_and(|t| !t.is_mine) && self.count_adjacent_mines(grid) == Some(0)
+        };
+        let mut revealed = std::collections::HashSet::new();
+        let mut count = 0;
+        for grid in self.grids().filter(|grid| is_zero(*grid)) {
+            if revealed.contains(&grid) {
+                continue;
+            }
+            count += 1;
+            let mut queue = VecDeque::from([grid]);
+            while let Some(grid) = queue.pop_front() {
+                if !revealed.insert(grid) {
+                    continue;
+                }
+                if is_zero(grid) {
+                    queue.extend(self.adjacent_grids(grid));
+                }
+            }
+        }
+        count
+            + self
+                .grids()
+                .filter(|grid| !revealed.contains(grid))
+                .filter(|grid| self.get(*grid).is_some_and(|t| !t.is_mine))
+                .count()
+    }
+
+    pub fn count_defused_mines(&self) -> usize {
+        self.board
+            .iter()
+            .filter(|tile_state| tile_state.is_defused())
+            .count()
+    }
+
     pub fn count_remaining_mines(&self) -> usize {
-        self.count_mines() - self.count_flagged_mines()
+        self.count_mines() - self.count_flagged_mines() - self.count_defused_mines()
+    }
+}
+
+/// FNV-1a, so that a seed string maps to the same board on every platform.
+fn seed_to_u64(seed: &str) -> u64 {
+    seed.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn send_outcome(
+    outcome: Outcome,
+    writer: &mut EventWriter<events::OnMoveTile>,
+    game_over_writer: &mut EventWriter<events::OnGameOver>,
+    game_clear_writer: &mut EventWriter<events::OnGameClear>,
+) {
+    if let Some(game_over) = outcome.game_over {
+        game_over_writer.send(game_over);
+    }
+    if let Some(move_tile) = outcome.move_tile {
+        writer.send(move_tile);
+    }
+    if outcome.is_cleared {
+        game_clear_writer.send(events::OnGameClear);
     }
 }
 
 fn on_try_open_tile_system(
     mut game_board: ResMut<GameBoard>,
+    game_rule: Res<GameRule>,
     mut reader: EventReader<events::OnTryOpenTile>,
     mut writer: EventWriter<events::OnMoveTile>,
     mut game_over_writer: EventWriter<events::OnGameOver>,
+    mut game_clear_writer: EventWriter<events::OnGameClear>,
 ) {
     for event in reader.iter() {
-        let mut open = false;
-        if let Some(tile_state) = game_board.get_mut(event.target) {
-            if !tile_state.is_open && !tile_state.is_flag {
-                tile_state.is_open = true;
-                open = true;
+        let is_mines_pending = game_board.is_mines_pending();
+        let outcome = game_board.try_open(event.target, &game_rule);
+        if let Some(report) = game_board.generation().filter(|_| is_mines_pending) {
+            if report.is_out_of_time {
+                warn!("{}: this seed may give a different board elsewhere", report);
+            } else {
+                info!("{}", report);
             }
-            if tile_state.is_mine {
-                game_over_writer.send(events::OnGameOver::Open {
-                    target: event.target,
-                });
-            }
-        }
-        if open {
-            let open_event = events::OnMoveTile::Open {
-                target: event.target,
-            };
-            game_board.move_stack.push_back(open_event);
-            writer.send(open_event);
         }
+        send_outcome(
+            outcome,
+            &mut writer,
+            &mut game_over_writer,
+            &mut game_clear_writer,
+        );
+    }
+}
+
+fn on_try_chord_tile_system(
+    mut game_board: ResMut<GameBoard>,
+    game_rule: Res<GameRule>,
+    mut reader: EventReader<events::OnTryChordTile>,
+    mut writer: EventWriter<events::OnMoveTile>,
+    mut game_over_writer: EventWriter<events::OnGameOver>,
+    mut game_clear_writer: EventWriter<events::OnGameClear>,
+) {
+    for event in reader.iter() {
+        let outcome = game_board.try_chord(event.target, &game_rule);
+        send_outcome(
+            outcome,
+            &mut writer,
+            &mut game_over_writer,
+            &mut game_clear_writer,
+        );
     }
 }
 
 fn on_try_flag_tile_system(
     mut game_board: ResMut<GameBoard>,
+    game_rule: Res<GameRule>,
     mut reader: EventReader<events::OnTryFlagTile>,
     mut writer: EventWriter<events::OnMoveTile>,
     mut game_over_writer: EventWriter<events::OnGameOver>,
     mut game_clear_writer: EventWriter<events::OnGameClear>,
 ) {
     for event in reader.iter() {
-        let mut flag = false;
-        if let Some(tile_state) = game_board.get_mut(event.target) {
-            if !tile_state.is_open && !tile_state.is_flag {
-                tile_state.is_flag = true;
-                flag = true;
-                if !tile_state.is_mine {
-                    game_over_writer.send(events::OnGameOver::Flag {
-                        target: event.target,
-                    });
-                }
-            }
-
-            if game_board.count_remaining_mines() == 0 {
-                game_clear_writer.send(events::OnGameClear);
-            }
-        }
-        if flag {
-            let flag_event = events::OnMoveTile::Flag {
-                target: event.target,
-            };
-            game_board.move_stack.push_back(flag_event);
-            writer.send(flag_event);
-        }
+        let outcome = game_board.try_flag(event.target, &game_rule);
+        send_outcome(
+            outcome,
+            &mut writer,
+            &mut game_over_writer,
+            &mut game_clear_writer,
+        );
     }
 }
 
 fn on_undo_system(
     mut game_board: ResMut<GameBoard>,
+    game_rule: Res<GameRule>,
     mut reader: EventReader<events::OnTryUndo>,
     mut writer: EventWriter<events::OnUndoTile>,
+    mut rescue_writer: EventWriter<events::OnRescue>,
 ) {
     for _ in reader.iter() {
-        if let Some(prev_move) = game_board.move_stack.pop_back() {
-            match prev_move {
-                OnMoveTile::Open { target } => {
-                    if let Some(tile_state) = game_board.get_mut(target) {
-                        tile_state.is_open = false;
+        if let Some(outcome) = game_board.try_undo(&game_rule) {
+            writer.send(outcome.undo_tile);
+            if outcome.is_rescue {
+                rescue_writer.send(events::OnRescue);
+            }
+        }
+    }
+}
+
+fn on_redo_system(
+    mut game_board: ResMut<GameBoard>,
+    game_rule: Res<GameRule>,
+    mut reader: EventReader<events::OnTryRedo>,
+    mut writer: EventWriter<events::OnMoveTile>,
+    mut redo_writer: EventWriter<events::OnRedoTile>,
+    mut game_over_writer: EventWriter<events::OnGameOver>,
+    mut game_clear_writer: EventWriter<events::OnGameClear>,
+) {
+    for _ in reader.iter() {
+        let outcome = game_board.try_redo(&game_rule);
+        if let Some(move_tile) = &outcome.move_tile {
+            redo_writer.send(events::OnRedoTile {
+                move_tile: move_tile.clone(),
+            });
+        }
+        send_outcome(
+            outcome,
+            &mut writer,
+            &mut game_over_writer,
+            &mut game_clear_writer,
+        );
+    }
+}
+
+fn on_retry_system(
+    mut game_board: ResMut<GameBoard>,
+    board_source: Res<BoardSource>,
+    mut reader: EventReader<events::OnRetry>,
+    mut writer: EventWriter<events::OnResetBoard>,
+) {
+    for event in reader.iter() {
+        match event {
+            events::OnRetry::SameBoard => game_board.reset(),
+            events::OnRetry::NewBoard => match board_source.build_fresh() {
+                Ok(new_board) => {
+                    if let Some(seed) = new_board.seed() {
+                        info!("board seed: {}", seed);
                     }
-                    writer.send(events::OnUndoTile::UnOpen { target });
+                    *game_board = new_board;
                 }
-                OnMoveTile::Flag { target } => {
-                    if let Some(tile_state) = game_board.get_mut(target) {
-                        tile_state.is_flag = false;
-                    }
-                    writer.send(events::OnUndoTile::UnFlag { target });
+                Err(err) => {
+                    error!("failed to build a new game board: {}", err);
+                    game_board.reset();
                 }
+            },
+        }
+        writer.send(events::OnResetBoard);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn mines(game_board: &GameBoard) -> Vec<PointyHexGrid> {
+        game_board
+            .grids()
+            .filter(|grid| game_board.get(*grid).unwrap().is_mine())
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_exact_mine_count() {
+        let game_board = GameBoard::generate(
+            BoardShape::hexagon(5),
+            MineCount::Exact(12),
+            "exact",
+            FirstClickSafety::None,
+        );
+        assert_eq!(game_board.count_mines(), 12);
+        assert_eq!(game_board.grids().count(), 61);
+    }
+
+    #[test]
+    fn test_generate_density() {
+        let game_board = GameBoard::generate(
+            BoardShape::hexagon(4),
+            MineCount::Density(0.25),
+            "density",
+            FirstClickSafety::None,
+        );
+        assert_eq!(game_board.count_mines(), 9);
+    }
+
+    #[test]
+    fn test_generate_same_seed_same_board() {
+        let generate = |seed| {
+            GameBoard::generate(
+                BoardShape::hexagon(6),
+                MineCount::Density(0.2),
+                seed,
+                FirstClickSafety::None,
+            )
+        };
+        assert_eq!(mines(&generate("hex")), mines(&generate("hex")));
+        assert_ne!(mines(&generate("hex")), mines(&generate("minesweeper")));
+    }
+
+    #[test]
+    fn test_first_click_opening_is_safe() {
+        let first_open = PointyHexGrid { x: 3, y: 3 };
+        for seed in ["a", "b", "c", "d"] {
+            let mut game_board = GameBoard::generate(
+                BoardShape::hexagon(4),
+                MineCount::Exact(20),
+                seed,
+                FirstClickSafety::Opening,
+            );
+            assert!(game_board.is_mines_pending());
+            assert_eq!(game_board.count_mines(), 20);
+
+            game_board.place_pending_mines(first_open);
+            assert!(!game_board.is_mines_pending());
+            assert_eq!(game_board.count_mines(), 20);
+            assert_eq!(game_board.count_adjacent_mines(first_open), Some(0));
+            assert!(!game_board.get(first_open).unwrap().is_mine());
+        }
+    }
+
+    #[test]
+    fn test_open_region_cascades_zero_tiles() {
+        let mut game_board = GameBoard::new(3);
+        let corner = PointyHexGrid { x: 4, y: 0 };
+        game_board.get_mut(corner).unwrap().is_mine = true;
+
+        let opened = game_board.open_region(PointyHexGrid { x: 0, y: 4 });
+        assert_eq!(opened.len(), game_board.grids().count() - 1);
+        assert!(!game_board.get(corner).unwrap().is_open());
+        assert!(game_board
+            .open_region(PointyHexGrid { x: 2, y: 2 })
+            .is_empty());
+    }
+
+    #[test]
+    fn test_open_region_stops_at_hints_and_flags() {
+        let mut game_board = GameBoard::new(3);
+        let center = PointyHexGrid { x: 2, y: 2 };
+        game_board.get_mut(center).unwrap().is_mine = true;
+        let flagged = PointyHexGrid { x: 0, y: 4 };
+        game_board.get_mut(flagged).unwrap().is_flag = true;
+
+        // 中心の隣はヒントなので一枚だけ開く
+        assert_eq!(
+            game_board.open_region(PointyHexGrid { x: 3, y: 2 }).len(),
+            1
+        );
+
+        let opened = game_board.open_region(PointyHexGrid { x: 4, y: 0 });
+        assert!(!opened.contains(&flagged));
+        assert!(!opened.contains(&center));
+        assert!(opened
+            .iter()
+            .all(|grid| game_board.get(*grid).unwrap().is_open()));
+    }
+
+    #[test]
+    fn test_chord_opens_when_flags_match() {
+        let mut game_board = GameBoard::new(3);
+        let target = PointyHexGrid { x: 2, y: 2 };
+        let mine = PointyHexGrid { x: 3, y: 2 };
+        game_board.get_mut(mine).unwrap().is_mine = true;
+        game_board
+            .get_mut(PointyHexGrid { x: 4, y: 0 })
+            .unwrap()
+            .is_mine = true;
+        game_board.get_mut(target).unwrap().is_open = true;
+
+        assert!(game_board.chord(target).is_empty());
+
+        game_board.get_mut(mine).unwrap().is_flag = true;
+        let opened = game_board.chord(target);
+        assert!(!opened.contains(&mine));
+        assert!(target
+            .neighbors()
+            .iter()
+            .filter(|grid| **grid != mine)
+            .all(|grid| game_board.get(*grid).unwrap().is_open()));
+    }
+
+    #[test]
+    fn test_chord_with_wrong_flag_opens_mine() {
+        let mut game_board = GameBoard::new(3);
+        let target = PointyHexGrid { x: 2, y: 2 };
+        let mine = PointyHexGrid { x: 3, y: 2 };
+        game_board.get_mut(mine).unwrap().is_mine = true;
+        game_board.get_mut(target).unwrap().is_open = true;
+        game_board
+            .get_mut(PointyHexGrid { x: 1, y: 2 })
+            .unwrap()
+            .is_flag = true;
+
+        assert!(game_board.chord(target).contains(&mine));
+    }
+
+    #[test]
+    fn test_reset_restores_original_layout() {
+        let mut game_board = GameBoard::load("1").unwrap();
+        let open_tiles = game_board.count_open_tile();
+        let mine_grids = mines(&game_board);
+
+        let mine = mine_grids[0];
+        game_board.push_move(OnMoveTile::Flag { target: mine });
+        game_board.get_mut(mine).unwrap().is_flag = true;
+        let safe = game_board
+            .grids()
+            .find(|grid| !game_board.get(*grid).unwrap().is_open() && !mine_grids.contains(grid))
+            .unwrap();
+        let opened = game_board.open_region(safe);
+        game_board.push_move(OnMoveTile::OpenRegion { targets: opened });
+
+        game_board.reset();
+        assert_eq!(game_board.count_open_tile(), open_tiles);
+        assert_eq!(game_board.count_flagged_mines(), 0);
+        assert_eq!(mines(&game_board), mine_grids);
+        assert!(game_board.undo().is_none());
+        assert!(game_board.redo().is_none());
+    }
+
+    #[test]
+    fn test_redo_reapplies_undone_moves() {
+        let mut game_board = GameBoard::new(3);
+        let open = PointyHexGrid { x: 2, y: 2 };
+        let flag = PointyHexGrid { x: 3, y: 2 };
+        game_board.get_mut(flag).unwrap().is_mine = true;
+
+        game_board.get_mut(open).unwrap().is_open = true;
+        game_board.push_move(OnMoveTile::Open { target: open });
+        game_board.get_mut(flag).unwrap().is_flag = true;
+        game_board.push_move(OnMoveTile::Flag { target: flag });
+
+        game_board.undo();
+        game_board.undo();
+        assert!(!game_board.get(open).unwrap().is_open());
+        assert!(!game_board.get(flag).unwrap().is_flag());
+
+        assert!(matches!(game_board.redo(), Some(OnMoveTile::Open { .. })));
+        assert!(game_board.get(open).unwrap().is_open());
+        assert!(matches!(game_board.redo(), Some(OnMoveTile::Flag { .. })));
+        assert!(game_board.get(flag).unwrap().is_flag());
+        assert!(game_board.redo().is_none());
+    }
+
+    #[test]
+    fn test_new_move_clears_redo_stack() {
+        let mut game_board = GameBoard::new(3);
+        let target = PointyHexGrid { x: 2, y: 2 };
+        game_board.push_move(OnMoveTile::Open { target });
+        game_board.undo();
+
+        game_board.push_move(OnMoveTile::Flag { target });
+        assert!(game_board.redo().is_none());
+    }
+
+    #[test]
+    fn test_win_rules() {
+        let mut game_board = GameBoard::new(2);
+        let mine = PointyHexGrid { x: 1, y: 1 };
+        game_board.get_mut(mine).unwrap().is_mine = true;
+        assert!(!game_board.is_cleared(WinRule::Either));
+
+        let safe_grids = game_board
+            .grids()
+            .filter(|grid| *grid != mine)
+            .collect::<Vec<_>>();
+        for grid in &safe_grids {
+            game_board.get_mut(*grid).unwrap().is_open = true;
+        }
+        assert!(game_board.is_cleared(WinRule::SafeTilesOpened));
+        assert!(!game_board.is_cleared(WinRule::MinesFlagged));
+        assert!(game_board.is_cleared(WinRule::Either));
+
+        for grid in &safe_grids[1..] {
+            game_board.get_mut(*grid).unwrap().is_open = false;
+        }
+        game_board.get_mut(mine).unwrap().is_flag = true;
+        assert!(!game_board.is_cleared(WinRule::SafeTilesOpened));
+        assert!(game_board.is_cleared(WinRule::MinesFlagged));
+
+        game_board.get_mut(safe_grids[1]).unwrap().is_flag = true;
+        assert!(!game_board.is_cleared(WinRule::MinesFlagged));
+    }
+
+    #[test]
+    fn test_opened_mine_is_never_cleared() {
+        let mut game_board = GameBoard::new(2);
+        let mine = PointyHexGrid { x: 1, y: 1 };
+        game_board.get_mut(mine).unwrap().is_mine = true;
+        for grid in game_board.grids().collect::<Vec<_>>() {
+            game_board.get_mut(grid).unwrap().is_open = true;
+        }
+        assert!(!game_board.is_cleared(WinRule::Either));
+    }
+
+    #[test]
+    fn test_cycle_marker() {
+        let mut game_board = GameBoard::new(2);
+        let target = PointyHexGrid { x: 1, y: 1 };
+
+        assert!(matches!(
+            game_board.cycle_marker(target),
+            Some(OnMoveTile::Flag { .. })
+        ));
+        assert!(game_board.get(target).unwrap().is_flag());
+        assert!(matches!(
+            game_board.cycle_marker(target),
+            Some(OnMoveTile::Question { .. })
+        ));
+        assert!(!game_board.get(target).unwrap().is_flag());
+        assert!(game_board.get(target).unwrap().is_question());
+        assert!(matches!(
+            game_board.cycle_marker(target),
+            Some(OnMoveTile::ClearMark { .. })
+        ));
+        assert!(!game_board.get(target).unwrap().is_question());
+
+        game_board.get_mut(target).unwrap().is_open = true;
+        assert!(game_board.cycle_marker(target).is_none());
+    }
+
+    #[test]
+    fn test_undo_marker_moves() {
+        let mut game_board = GameBoard::new(2);
+        let target = PointyHexGrid { x: 1, y: 1 };
+        for _ in 0..3 {
+            let marker_event = game_board.cycle_marker(target).unwrap();
+            game_board.push_move(marker_event);
+        }
+
+        game_board.undo();
+        assert!(game_board.get(target).unwrap().is_question());
+        game_board.undo();
+        assert!(game_board.get(target).unwrap().is_flag());
+        assert!(!game_board.get(target).unwrap().is_question());
+        game_board.undo();
+        assert!(!game_board.get(target).unwrap().is_flag());
+    }
+
+    #[test]
+    fn test_lives_defuse_mines_until_the_last() {
+        let mut game_board = GameBoard::new(3);
+        let mines = [PointyHexGrid { x: 2, y: 2 }, PointyHexGrid { x: 4, y: 0 }];
+        for mine in mines {
+            game_board.get_mut(mine).unwrap().is_mine = true;
+        }
+
+        let opened = game_board.open_region(mines[0]);
+        assert_eq!(game_board.defuse_opened_mines(&opened, 2), None);
+        assert!(game_board.get(mines[0]).unwrap().is_defused());
+        assert_eq!(game_board.count_lives_left(2), 1);
+        assert_eq!(game_board.count_remaining_mines(), 1);
+
+        let opened = game_board.open_region(mines[1]);
+        assert_eq!(game_board.defuse_opened_mines(&opened, 2), Some(mines[1]));
+        assert!(game_board.get(mines[1]).unwrap().is_exploded());
+        assert!(!game_board.is_cleared(WinRule::Either));
+    }
+
+    #[test]
+    fn test_undo_restores_life() {
+        let mut game_board = GameBoard::new(2);
+        let mine = PointyHexGrid { x: 1, y: 1 };
+        game_board.get_mut(mine).unwrap().is_mine = true;
+
+        let opened = game_board.open_region(mine);
+        game_board.defuse_opened_mines(&opened, 3);
+        game_board.push_move(OnMoveTile::Open { target: mine });
+        assert_eq!(game_board.count_lives_left(3), 2);
+
+        game_board.undo();
+        assert_eq!(game_board.count_lives_left(3), 3);
+        game_board.redo();
+        assert_eq!(game_board.count_lives_left(3), 2);
+    }
+
+    #[test]
+    fn test_count_3bv() {
+        // 地雷がなければ一回で全部開く
+        let mut game_board = GameBoard::new(3);
+        assert_eq!(game_board.count_3bv(), 1);
+
+        // 中心の地雷の周り 6 枚はどれも外周の 0 から開く
+        game_board
+            .get_mut(PointyHexGrid { x: 2, y: 2 })
+            .unwrap()
+            .is_mine = true;
+        assert_eq!(game_board.count_3bv(), 1);
+
+        // 全部地雷のときは数えるタイルがない
+        let mut game_board = GameBoard::new(2);
+        for grid in game_board.grids().collect::<Vec<_>>() {
+            game_board.get_mut(grid).unwrap().is_mine = true;
+        }
+        assert_eq!(game_board.count_3bv(), 0);
+    }
+
+    #[test]
+    fn test_count_3bv_isolated_hints() {
+        // 中心以外が地雷なら中心は一回開ける必要がある
+        let mut game_board = GameBoard::new(2);
+        let center = PointyHexGrid { x: 1, y: 1 };
+        for grid in game_board.adjacent_grids(center).collect::<Vec<_>>() {
+            game_board.get_mut(grid).unwrap().is_mine = true;
+        }
+        assert_eq!(game_board.count_3bv(), 1);
+
+        game_board
+            .get_mut(PointyHexGrid { x: 2, y: 1 })
+            .unwrap()
+            .is_mine = false;
+        assert_eq!(game_board.count_3bv(), 2);
+    }
+
+    #[test]
+    fn test_first_click_is_deterministic() {
+        let generate = |first_open| {
+            let mut game_board = GameBoard::generate(
+                BoardShape::hexagon(5),
+                MineCount::Exact(15),
+                "seed",
+                FirstClickSafety::Tile,
+            );
+            game_board.place_pending_mines(first_open);
+            mines(&game_board)
+        };
+        let first_open = PointyHexGrid { x: 1, y: 5 };
+        assert_eq!(generate(first_open), generate(first_open));
+        assert!(!generate(first_open).contains(&first_open));
+    }
+
+    #[test]
+    fn test_is_out_of_bound() {
+        for tiles_per_side in 1..=8 {
+            let game_board = GameBoard::new(tiles_per_side);
+            let width = (2 * tiles_per_side - 1) as i32;
+            let in_bound = (-1..=width)
+                .flat_map(|y| (-1..=width).map(move |x| PointyHexGrid { x, y }))
+                .filter(|grid| !game_board.is_out_of_bound(*grid))
+                .count();
+            assert_eq!(in_bound, 3 * tiles_per_side * (tiles_per_side - 1) + 1);
+            assert_eq!(game_board.grids().count(), in_bound);
+        }
+
+        let game_board = GameBoard::new(3);
+        let corners = [[2, 0], [4, 0], [4, 2], [2, 4], [0, 4], [0, 2]];
+        for [x, y] in corners {
+            assert!(!game_board.is_out_of_bound(PointyHexGrid { x, y }));
+        }
+        let outside = [[1, 0], [0, 1], [4, 3], [3, 4], [5, 0], [-1, 4], [2, -1]];
+        for [x, y] in outside {
+            assert!(game_board.is_out_of_bound(PointyHexGrid { x, y }));
+        }
+    }
+
+    #[test]
+    fn test_count_adjacent_mines() {
+        let game_board = board_file::parse("2\n#MM\n...\nM.#").unwrap();
+        let count = |x, y| game_board.count_adjacent_mines(PointyHexGrid { x, y });
+        assert_eq!(count(1, 1), Some(3));
+        assert_eq!(count(2, 1), Some(1));
+        assert_eq!(count(0, 1), Some(2));
+        assert_eq!(count(1, 2), Some(1));
+        assert_eq!(count(0, 0), None);
+        assert_eq!(count(5, 5), None);
+    }
+
+    #[test]
+    fn test_count_remaining_mines() {
+        let mut game_board = board_file::parse("2\n#MM\n...\nM.#").unwrap();
+        assert_eq!(game_board.count_remaining_mines(), 3);
+        game_board.cycle_marker(PointyHexGrid { x: 1, y: 0 });
+        assert_eq!(game_board.count_remaining_mines(), 2);
+        // 安全なタイルの旗は数えない
+        game_board.cycle_marker(PointyHexGrid { x: 1, y: 1 });
+        assert_eq!(game_board.count_remaining_mines(), 2);
+        let opened = game_board.open_region(PointyHexGrid { x: 0, y: 2 });
+        assert_eq!(game_board.defuse_opened_mines(&opened, 2), None);
+        assert_eq!(game_board.count_remaining_mines(), 1);
+    }
+
+    #[test]
+    fn test_load_board_file() {
+        let game_board = GameBoard::load("1").unwrap();
+        let text = fs::read_to_string(format!("{}/1.txt", BOARDS_DIR)).unwrap();
+        assert_eq!(game_board.shape(), &BoardShape::hexagon(4));
+        assert_eq!(game_board.count_mines(), text.matches('M').count());
+        assert_eq!(game_board.count_open_tile(), text.matches('O').count());
+        assert!(matches!(
+            GameBoard::load("no such board"),
+            Err(BoardParseError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_wrong_tile_count() {
+        let text = ron::to_string(&GameBoard::new(2)).unwrap();
+        assert!(ron::from_str::<GameBoard>(&text).is_ok());
+        let text = text.replacen("tiles_per_side:2", "tiles_per_side:3", 1);
+        assert!(ron::from_str::<GameBoard>(&text).is_err());
+    }
+
+    fn board_strategy() -> impl Strategy<Value = GameBoard> {
+        (1_usize..8, 0.0_f32..0.5, "[a-z]{1,8}").prop_map(|(tiles_per_side, density, seed)| {
+            GameBoard::generate(
+                BoardShape::hexagon(tiles_per_side),
+                MineCount::Density(density),
+                &seed,
+                FirstClickSafety::None,
+            )
+        })
+    }
+
+    /// Open (`false`) or flag (`true`) inputs on a board up to 7 tiles per side.
+    fn inputs_strategy() -> impl Strategy<Value = Vec<(bool, PointyHexGrid)>> {
+        prop::collection::vec(
+            (any::<bool>(), 0..13, 0..13)
+                .prop_map(|(is_flag, x, y)| (is_flag, PointyHexGrid { x, y })),
+            0..30,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_every_tile_has_2_to_6_neighbors(tiles_per_side in 2_usize..12) {
+            let game_board = GameBoard::new(tiles_per_side);
+            for grid in game_board.grids() {
+                let neighbors = game_board.adjacent_grids(grid).count();
+                prop_assert!((2..=6).contains(&neighbors), "{:?} has {}", grid, neighbors);
+            }
+        }
+
+        #[test]
+        fn prop_count_adjacent_mines_matches_neighbors(game_board in board_strategy()) {
+            for grid in game_board.grids() {
+                let mines = game_board
+                    .adjacent_grids(grid)
+                    .filter(|neighbor| game_board.get(*neighbor).unwrap().is_mine())
+                    .count();
+                prop_assert_eq!(game_board.count_adjacent_mines(grid), Some(mines));
+            }
+        }
+
+        #[test]
+        fn prop_count_remaining_mines(game_board in board_strategy(), inputs in inputs_strategy()) {
+            let mut game_board = game_board;
+            for (_, grid) in inputs {
+                game_board.cycle_marker(grid);
+            }
+            let flagged_mines = game_board
+                .grids()
+                .filter(|grid| {
+                    let tile_state = game_board.get(*grid).unwrap();
+                    tile_state.is_mine() && tile_state.is_flag()
+                })
+                .count();
+            prop_assert_eq!(
+                game_board.count_remaining_mines(),
+                game_board.count_mines() - flagged_mines
+            );
+        }
+
+        #[test]
+        fn prop_undo_restores_the_board(game_board in board_strategy(), inputs in inputs_strategy()) {
+            let original = game_board.clone();
+            let mut game_board = game_board;
+            let rule = GameRule {
+                practice: true,
+                strict_flag: false,
+                ..Default::default()
+            };
+            for (is_flag, grid) in inputs {
+                if is_flag {
+                    game_board.try_flag(grid, &rule);
+                } else {
+                    game_board.try_open(grid, &rule);
+                }
+            }
+            while game_board.try_undo(&rule).is_some() {}
+            prop_assert!(!game_board.is_game_over());
+            for grid in original.grids() {
+                prop_assert_eq!(game_board.get(grid), original.get(grid));
+            }
+        }
+
+        #[test]
+        fn prop_board_file_round_trips(game_board in board_strategy(), inputs in inputs_strategy()) {
+            let mut game_board = game_board;
+            for (is_flag, grid) in inputs {
+                if is_flag {
+                    game_board.cycle_marker(grid);
+                } else if game_board.get(grid).is_some_and(|t| !t.is_mine()) {
+                    game_board.open_region(grid);
+                }
+            }
+            let parsed = board_file::parse(&game_board.to_board_text()).unwrap();
+            prop_assert_eq!(parsed.shape(), game_board.shape());
+            // 開いたタイルに残った ? マークは書き出されない
+            let visible = |tile_state: &TileState| {
+                let is_closed = !tile_state.is_open();
+                (
+                    tile_state.is_open(),
+                    tile_state.is_mine(),
+                    is_closed && tile_state.is_flag(),
+                    is_closed && tile_state.is_question(),
+                )
+            };
+            for grid in game_board.grids() {
+                prop_assert_eq!(
+                    parsed.get(grid).map(visible),
+                    game_board.get(grid).map(visible)
+                );
             }
         }
     }
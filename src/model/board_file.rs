@@ -0,0 +1,580 @@
+use super::{BoardShape, GameBoard, TileState, WinRule};
+use crate::hexgrid::PointyHexGrid;
+use bevy::prelude::default;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+
+/// Latest version of the board format, written by `write`.
+pub const BOARD_FORMAT_VERSION: u32 = 3;
+
+/// Optional header of a v2 or later board file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub difficulty: Option<String>,
+    /// Win rule the board is meant to be played with, overriding `GameRule::win_rule`.
+    pub win_rule: Option<WinRule>,
+    /// Number of mines on the board. Checked against the grid when parsing.
+    pub mine_count: Option<usize>,
+}
+
+/// Why a board file could not be read. Lines and columns are 1-based.
+#[derive(Debug)]
+pub enum BoardParseError {
+    Io(io::Error),
+    /// The `version` line names a version this build cannot read.
+    UnsupportedVersion {
+        line: usize,
+        found: String,
+    },
+    /// A header line is not `key: value` with a known key.
+    InvalidHeader {
+        line: usize,
+        found: String,
+    },
+    InvalidHeaderValue {
+        line: usize,
+        key: String,
+        found: String,
+    },
+    /// The first line of the grid is not a positive `tiles_per_side`.
+    InvalidTilesPerSide {
+        line: usize,
+        found: String,
+    },
+    RowCount {
+        expected: usize,
+        found: usize,
+    },
+    RowLength {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnexpectedChar {
+        line: usize,
+        column: usize,
+        expected: &'static str,
+        found: char,
+    },
+    /// The `mines` header disagrees with the mines on the grid.
+    MineCountMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for BoardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::UnsupportedVersion { line, found } => write!(
+                f,
+                "line {}: unsupported board format version {:?}",
+                line, found
+            ),
+            Self::InvalidHeader { line, found } => write!(
+                f,
+                "line {}: expected a `key: value` header but found {:?}",
+                line, found
+            ),
+            Self::InvalidHeaderValue { line, key, found } => write!(
+                f,
+                "line {}: invalid value {:?} for header {:?}",
+                line, found, key
+            ),
+            Self::InvalidTilesPerSide { line, found } => write!(
+                f,
+                "line {}: expected the tiles per side of a valid hexagon but found {:?}",
+                line, found
+            ),
+            Self::RowCount { expected, found } => {
+                write!(f, "expected {} rows but found {}", expected, found)
+            }
+            Self::RowLength {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} tiles but found {}",
+                line, expected, found
+            ),
+            Self::UnexpectedChar {
+                line,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: expected {} but found {:?}",
+                line, column, expected, found
+            ),
+            Self::MineCountMismatch { expected, found } => write!(
+                f,
+                "header says {} mines but the board has {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoardParseError {}
+
+impl From<io::Error> for BoardParseError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Parses a board file of any version.
+///
+/// v1 is `tiles_per_side` on the first line, then `2 * tiles_per_side - 1`
+/// rows where `#` is outside the board, `.` a closed tile, `O` an opened tile
+/// and `M` a mine.
+///
+/// v2 starts with a `version 2` line and `key: value` metadata lines ended by
+/// `---`, followed by a v1 grid which may also use `F`/`f` for a flagged
+/// mine/safe tile and `Q`/`?` for a question-marked mine/safe tile.
+///
+/// v3 may also have a `shape` header such as `shape: ring 5 2`, in which case
+/// the grid has no size line and its rows cover the `BoardShape::size()`
+/// rectangle. The tiles of a `mask` shape are the cells that are not `#`.
+pub fn parse(text: &str) -> Result<GameBoard, BoardParseError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .peekable();
+
+    let version = match lines.peek() {
+        Some((line, first)) if first.starts_with("version") => {
+            let (line, found) = (*line, first["version".len()..].trim());
+            lines.next();
+            match found.parse::<u32>() {
+                Ok(version @ 1..=BOARD_FORMAT_VERSION) => version,
+                _ => {
+                    return Err(BoardParseError::UnsupportedVersion {
+                        line,
+                        found: found.to_string(),
+                    })
+                }
+            }
+        }
+        _ => 1,
+    };
+
+    let mut metadata = BoardMetadata::default();
+    let mut shape = None;
+    if version >= 2 {
+        for (line, header) in lines.by_ref() {
+            if header == "---" {
+                break;
+            }
+            match header.split_once(':') {
+                Some((key, value)) if version >= 3 && key.trim() == "shape" => {
+                    shape = Some(BoardShape::parse(value).ok_or_else(|| {
+                        BoardParseError::InvalidHeaderValue {
+                            line,
+                            key: "shape".to_string(),
+                            found: value.trim().to_string(),
+                        }
+                    })?);
+                }
+                _ => parse_header(&mut metadata, line, header)?,
+            }
+        }
+    }
+
+    // 形の指定がなければ v1 と同じく最初の行の大きさの六角形
+    let shape = match shape {
+        Some(shape) => shape,
+        None => parse_hexagon(lines.next())?,
+    };
+
+    let (width, height) = shape.size();
+    let rows = lines.collect::<Vec<_>>();
+    if rows.len() != height {
+        return Err(BoardParseError::RowCount {
+            expected: height,
+            found: rows.len(),
+        });
+    }
+    for (line, row) in &rows {
+        if row.chars().count() != width {
+            return Err(BoardParseError::RowLength {
+                line: *line,
+                expected: width,
+                found: row.chars().count(),
+            });
+        }
+    }
+
+    // マスクは # 以外のマスがタイルになる
+    let shape = match shape {
+        BoardShape::Mask { width, height, .. } => BoardShape::Mask {
+            width,
+            height,
+            tiles: rows
+                .iter()
+                .flat_map(|(_, row)| row.chars().map(|c| c != '#'))
+                .collect(),
+        },
+        shape => shape,
+    };
+
+    let mut board = GameBoard::from_shape(shape);
+    for (y, (line, row)) in rows.into_iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let grid = PointyHexGrid {
+                x: x as i32,
+                y: y as i32,
+            };
+            let unexpected = |expected| BoardParseError::UnexpectedChar {
+                line,
+                column: x + 1,
+                expected,
+                found: c,
+            };
+            match board.get_mut(grid) {
+                Some(tile_state) => {
+                    *tile_state = match (version, c) {
+                        (_, '.') => default(),
+                        (_, 'O') => TileState {
+                            is_open: true,
+                            ..default()
+                        },
+                        (_, 'M') => TileState {
+                            is_mine: true,
+                            ..default()
+                        },
+                        (2.., 'F' | 'f') => TileState {
+                            is_mine: c == 'F',
+                            is_flag: true,
+                            ..default()
+                        },
+                        (2.., 'Q' | '?') => TileState {
+                            is_mine: c == 'Q',
+                            is_question: true,
+                            ..default()
+                        },
+                        (1, _) => return Err(unexpected("'.', 'O' or 'M'")),
+                        _ => return Err(unexpected("'.', 'O', 'M', 'F', 'f', 'Q' or '?'")),
+                    }
+                }
+                None if c != '#' => return Err(unexpected("'#'")),
+                None => {}
+            }
+        }
+    }
+
+    if let Some(expected) = metadata.mine_count {
+        let found = board.count_mines();
+        if expected != found {
+            return Err(BoardParseError::MineCountMismatch { expected, found });
+        }
+    }
+    board.metadata = metadata;
+
+    Ok(board)
+}
+
+// v3 の shape ヘッダと同じく，大きすぎる盤面も読まない
+fn parse_hexagon(line: Option<(usize, &str)>) -> Result<BoardShape, BoardParseError> {
+    match line {
+        Some((line, found)) => match found.parse::<usize>().map(BoardShape::hexagon) {
+            Ok(shape) if shape.is_valid() => Ok(shape),
+            _ => Err(BoardParseError::InvalidTilesPerSide {
+                line,
+                found: found.to_string(),
+            }),
+        },
+        None => Err(BoardParseError::InvalidTilesPerSide {
+            line: 1,
+            found: String::new(),
+        }),
+    }
+}
+
+fn parse_header(
+    metadata: &mut BoardMetadata,
+    line: usize,
+    header: &str,
+) -> Result<(), BoardParseError> {
+    let invalid_header = || BoardParseError::InvalidHeader {
+        line,
+        found: header.to_string(),
+    };
+    let (key, value) = header.split_once(':').ok_or_else(invalid_header)?;
+    let (key, value) = (key.trim(), value.trim());
+    let invalid_value = || BoardParseError::InvalidHeaderValue {
+        line,
+        key: key.to_string(),
+        found: value.to_string(),
+    };
+    match key {
+        "title" => metadata.title = Some(value.to_string()),
+        "author" => metadata.author = Some(value.to_string()),
+        "difficulty" => metadata.difficulty = Some(value.to_string()),
+        "win_rule" => {
+            let win_rule = WinRule::ALL.into_iter().find(|rule| rule.name() == value);
+            metadata.win_rule = Some(win_rule.ok_or_else(invalid_value)?);
+        }
+        "mines" => metadata.mine_count = Some(value.parse().map_err(|_| invalid_value())?),
+        _ => return Err(invalid_header()),
+    }
+    Ok(())
+}
+
+/// Writes `board` in the latest format, so that `parse` reads it back.
+///
+/// Opened mines are written closed, and mines that are still pending are not
+/// written at all.
+pub fn write(board: &GameBoard) -> String {
+    let metadata = board.metadata();
+    let mut text = format!("version {}\n", BOARD_FORMAT_VERSION);
+    let headers = [
+        ("shape", Some(board.shape().to_string())),
+        ("title", metadata.title.clone()),
+        ("author", metadata.author.clone()),
+        ("difficulty", metadata.difficulty.clone()),
+        (
+            "win_rule",
+            metadata
+                .win_rule
+                .map(|win_rule| win_rule.name().to_string()),
+        ),
+        ("mines", metadata.mine_count.map(|count| count.to_string())),
+    ];
+    for (key, value) in headers {
+        if let Some(value) = value {
+            let _ = writeln!(text, "{}: {}", key, value);
+        }
+    }
+    text.push_str("---\n");
+
+    let (width, height) = board.shape().size();
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let c = match board.get(PointyHexGrid { x, y }) {
+                None => '#',
+                // 開いたタイルに残っている ? マークは見えないので書かない
+                Some(tile_state) => match (tile_state.is_mine(), tile_state) {
+                    (true, t) if t.is_open() => 'M',
+                    (false, t) if t.is_open() => 'O',
+                    (true, t) if t.is_flag() => 'F',
+                    (false, t) if t.is_flag() => 'f',
+                    (true, t) if t.is_question() => 'Q',
+                    (false, t) if t.is_question() => '?',
+                    (true, _) => 'M',
+                    (false, _) => '.',
+                },
+            };
+            text.push(c);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let board = parse("2\n#.M\nO..\n..#\n").unwrap();
+        assert_eq!(board.shape(), &BoardShape::hexagon(2));
+        assert_eq!(board.count_mines(), 1);
+        assert_eq!(board.count_open_tile(), 1);
+        assert!(board.get(PointyHexGrid { x: 2, y: 0 }).unwrap().is_mine());
+    }
+
+    #[test]
+    fn test_parse_invalid_tiles_per_side() {
+        assert!(matches!(
+            parse("\n\nfour\n"),
+            Err(BoardParseError::InvalidTilesPerSide { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse("0"),
+            Err(BoardParseError::InvalidTilesPerSide { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse(""),
+            Err(BoardParseError::InvalidTilesPerSide { .. })
+        ));
+        assert!(matches!(
+            parse("1000000\n"),
+            Err(BoardParseError::InvalidTilesPerSide { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_dimension_mismatch() {
+        assert!(matches!(
+            parse("2\n#..\n..."),
+            Err(BoardParseError::RowCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            parse("2\n#..\n....\n..#"),
+            Err(BoardParseError::RowLength {
+                line: 3,
+                expected: 3,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_unexpected_char() {
+        assert!(matches!(
+            parse("2\n#..\n.x.\n..#"),
+            Err(BoardParseError::UnexpectedChar {
+                line: 3,
+                column: 2,
+                found: 'x',
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("2\n...\n...\n..#"),
+            Err(BoardParseError::UnexpectedChar {
+                line: 2,
+                column: 1,
+                expected: "'#'",
+                found: '.',
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_message() {
+        let err = parse("2\n#..\n.x.\n..#").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 2: expected '.', 'O' or 'M' but found 'x'"
+        );
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let text = "version 2\n\
+                    title: First steps\n\
+                    author: gizmrkv\n\
+                    win_rule: mines_flagged\n\
+                    mines: 2\n\
+                    ---\n\
+                    2\n#F?\nO.Q\nf.#\n";
+        let board = parse(text).unwrap();
+        let metadata = board.metadata();
+        assert_eq!(metadata.title.as_deref(), Some("First steps"));
+        assert_eq!(metadata.author.as_deref(), Some("gizmrkv"));
+        assert_eq!(metadata.difficulty, None);
+        assert_eq!(metadata.win_rule, Some(WinRule::MinesFlagged));
+        assert_eq!(board.count_mines(), 2);
+        assert_eq!(board.count_flags(), 2);
+        assert_eq!(board.count_wrong_flags(), 1);
+        assert!(board
+            .get(PointyHexGrid { x: 2, y: 0 })
+            .unwrap()
+            .is_question());
+        assert!(board.get(PointyHexGrid { x: 2, y: 1 }).unwrap().is_mine());
+    }
+
+    #[test]
+    fn test_parse_v1_rejects_v2_glyphs() {
+        assert!(matches!(
+            parse("2\n#F.\n...\n..#"),
+            Err(BoardParseError::UnexpectedChar {
+                line: 2,
+                column: 2,
+                found: 'F',
+                ..
+            })
+        ));
+        assert!(parse("version 1\n2\n#..\nO..\n..#").is_ok());
+    }
+
+    #[test]
+    fn test_parse_invalid_header() {
+        assert!(matches!(
+            parse("version 4\n---\n2\n#..\n...\n..#"),
+            Err(BoardParseError::UnsupportedVersion { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("version 2\ncolour: red\n---\n2\n#..\n...\n..#"),
+            Err(BoardParseError::InvalidHeader { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse("version 2\nwin_rule: never\n---\n2\n#..\n...\n..#"),
+            Err(BoardParseError::InvalidHeaderValue { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse("version 2\nmines: 3\n---\n2\n#.M\n...\n..#"),
+            Err(BoardParseError::MineCountMismatch {
+                expected: 3,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_v3_shape() {
+        let board = parse("version 3\nshape: triangle 3\n---\n..M\nO.#\n.##\n").unwrap();
+        assert_eq!(board.shape(), &BoardShape::Triangle { tiles_per_side: 3 });
+        assert_eq!(board.grids().count(), 6);
+        assert_eq!(board.count_mines(), 1);
+        assert!(matches!(
+            parse("version 3\nshape: triangle 3\n---\n...\n...\n.##\n"),
+            Err(BoardParseError::UnexpectedChar {
+                line: 5,
+                column: 3,
+                expected: "'#'",
+                ..
+            })
+        ));
+
+        let board = parse("version 3\nshape: mask 3 2\n---\n#.M\nO.#\n").unwrap();
+        assert_eq!(board.grids().count(), 4);
+        assert!(board.get(PointyHexGrid { x: 0, y: 0 }).is_none());
+        assert_eq!(parse(&write(&board)).unwrap().shape(), board.shape());
+
+        assert!(matches!(
+            parse("version 3\nshape: circle 3\n---\n..."),
+            Err(BoardParseError::InvalidHeaderValue { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse("version 2\nshape: triangle 3\n---\n..M\nO.#\n.##\n"),
+            Err(BoardParseError::InvalidHeader { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut board = parse("2\n#.M\nO..\nM.#\n").unwrap();
+        board.metadata.title = Some("Round trip".to_string());
+        board.metadata.win_rule = Some(WinRule::SafeTilesOpened);
+        board.cycle_marker(PointyHexGrid { x: 2, y: 0 });
+        board.cycle_marker(PointyHexGrid { x: 1, y: 1 });
+        board.cycle_marker(PointyHexGrid { x: 1, y: 1 });
+
+        let text = write(&board);
+        assert_eq!(
+            text,
+            "version 3\nshape: hexagon 2\ntitle: Round trip\nwin_rule: safe_tiles_opened\n---\n#.F\nO?.\nM.#\n"
+        );
+        let parsed = parse(&text).unwrap();
+        assert_eq!(parsed.metadata(), board.metadata());
+        for grid in board.grids() {
+            assert_eq!(parsed.get(grid), board.get(grid));
+        }
+    }
+}
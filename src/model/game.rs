@@ -0,0 +1,334 @@
+use super::{BoardParseError, BoardSource, GameBoard, GameRule};
+use crate::events::{OnGameOver, OnMoveTile, OnUndoTile};
+use crate::hexgrid::PointyHexGrid;
+
+/// What a player input did to the board.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// The move that was made, or `None` if the input changed nothing.
+    pub move_tile: Option<OnMoveTile>,
+    /// Set when the move ended the game.
+    pub game_over: Option<OnGameOver>,
+    /// Set when the move cleared the board.
+    pub is_cleared: bool,
+}
+
+impl Outcome {
+    pub fn is_ignored(&self) -> bool {
+        self.move_tile.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoOutcome {
+    pub undo_tile: OnUndoTile,
+    /// The undone move had ended the game, which is on again.
+    pub is_rescue: bool,
+}
+
+// ルールは GameBoard と GameRule だけで決まるので，Bevy の外からも同じ処理を使う
+impl GameBoard {
+    /// Opens `target`, placing the mines first if they are still pending.
+    pub fn try_open(&mut self, target: PointyHexGrid, rule: &GameRule) -> Outcome {
+        if self.is_game_over {
+            return Outcome::default();
+        }
+        self.place_pending_mines(target);
+        let opened = self.open_region(target);
+        self.finish_open(opened, rule)
+    }
+
+    /// Opens the closed neighbours of the hint tile `target` if its flags are all placed.
+    pub fn try_chord(&mut self, target: PointyHexGrid, rule: &GameRule) -> Outcome {
+        if self.is_game_over {
+            return Outcome::default();
+        }
+        let opened = self.chord(target);
+        self.finish_open(opened, rule)
+    }
+
+    fn finish_open(&mut self, opened: Vec<PointyHexGrid>, rule: &GameRule) -> Outcome {
+        if opened.is_empty() {
+            return Outcome::default();
+        }
+        let game_over = self
+            .defuse_opened_mines(&opened, rule.lives)
+            .map(|target| OnGameOver::Open { target });
+        let move_tile = if let [target] = opened[..] {
+            OnMoveTile::Open { target }
+        } else {
+            OnMoveTile::OpenRegion { targets: opened }
+        };
+        self.push_move(move_tile.clone());
+        self.finish_move(move_tile, game_over, rule)
+    }
+
+    /// Cycles the marker on `target`. Flagging a safe tile loses under `GameRule::strict_flag`.
+    pub fn try_flag(&mut self, target: PointyHexGrid, rule: &GameRule) -> Outcome {
+        // 地雷が置かれる前に旗を立てても意味がない
+        if self.is_game_over || self.is_mines_pending() {
+            return Outcome::default();
+        }
+        let Some(move_tile) = self.cycle_marker(target) else {
+            return Outcome::default();
+        };
+        let game_over = match move_tile {
+            OnMoveTile::Flag { target } if rule.strict_flag && !self.is_mine_at(target) => {
+                Some(OnGameOver::Flag { target })
+            }
+            _ => None,
+        };
+        self.push_move(move_tile.clone());
+        self.finish_move(move_tile, game_over, rule)
+    }
+
+    /// Takes back the last move. Undoing the move that ended the game is only allowed
+    /// under `GameRule::practice`, and counts as a rescue.
+    pub fn try_undo(&mut self, rule: &GameRule) -> Option<UndoOutcome> {
+        if self.is_game_over && !rule.practice {
+            return None;
+        }
+        let is_rescue = self.is_game_over;
+        let undo_tile = self.undo()?;
+        if is_rescue {
+            self.is_game_over = false;
+            self.rescue_count += 1;
+        }
+        Some(UndoOutcome {
+            undo_tile,
+            is_rescue,
+        })
+    }
+
+    /// Plays the last undone move again, with the same consequences it had.
+    pub fn try_redo(&mut self, rule: &GameRule) -> Outcome {
+        if self.is_game_over {
+            return Outcome::default();
+        }
+        let Some(move_tile) = self.redo() else {
+            return Outcome::default();
+        };
+        let game_over = match &move_tile {
+            OnMoveTile::Open { .. } | OnMoveTile::OpenRegion { .. } => move_tile
+                .targets()
+                .iter()
+                .find(|grid| self.get(**grid).is_some_and(|t| t.is_exploded()))
+                .map(|target| OnGameOver::Open { target: *target }),
+            OnMoveTile::Flag { target } if rule.strict_flag && !self.is_mine_at(*target) => {
+                Some(OnGameOver::Flag { target: *target })
+            }
+            _ => None,
+        };
+        self.finish_move(move_tile, game_over, rule)
+    }
+
+    fn finish_move(
+        &mut self,
+        move_tile: OnMoveTile,
+        game_over: Option<OnGameOver>,
+        rule: &GameRule,
+    ) -> Outcome {
+        if game_over.is_some() {
+            self.is_game_over = true;
+        }
+        Outcome {
+            move_tile: Some(move_tile),
+            game_over,
+            is_cleared: self.is_cleared(self.win_rule(rule.win_rule)),
+        }
+    }
+
+    fn is_mine_at(&self, grid: PointyHexGrid) -> bool {
+        self.get(grid).is_some_and(|t| t.is_mine)
+    }
+}
+
+/// A game driven without Bevy, e.g. from a test, a bot or a CLI.
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: GameBoard,
+    rule: GameRule,
+}
+
+impl Game {
+    pub fn new(board: GameBoard, rule: GameRule) -> Self {
+        Self { board, rule }
+    }
+
+    pub fn from_source(source: &BoardSource, rule: GameRule) -> Result<Self, BoardParseError> {
+        Ok(Self::new(source.build()?, rule))
+    }
+
+    pub fn board(&self) -> &GameBoard {
+        &self.board
+    }
+
+    pub fn rule(&self) -> &GameRule {
+        &self.rule
+    }
+
+    pub fn into_board(self) -> GameBoard {
+        self.board
+    }
+
+    // 勝った後の盤面は動かさない
+    pub fn open(&mut self, target: PointyHexGrid) -> Outcome {
+        if self.is_cleared() {
+            return Outcome::default();
+        }
+        self.board.try_open(target, &self.rule)
+    }
+
+    pub fn chord(&mut self, target: PointyHexGrid) -> Outcome {
+        if self.is_cleared() {
+            return Outcome::default();
+        }
+        self.board.try_chord(target, &self.rule)
+    }
+
+    pub fn flag(&mut self, target: PointyHexGrid) -> Outcome {
+        if self.is_cleared() {
+            return Outcome::default();
+        }
+        self.board.try_flag(target, &self.rule)
+    }
+
+    pub fn undo(&mut self) -> Option<UndoOutcome> {
+        self.board.try_undo(&self.rule)
+    }
+
+    pub fn redo(&mut self) -> Outcome {
+        self.board.try_redo(&self.rule)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.board.is_game_over()
+    }
+
+    pub fn is_cleared(&self) -> bool {
+        self.board
+            .is_cleared(self.board.win_rule(self.rule.win_rule))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{board_file, BoardShape, FirstClickSafety, MineCount, WinRule};
+
+    fn grid(x: i32, y: i32) -> PointyHexGrid {
+        PointyHexGrid { x, y }
+    }
+
+    // 地雷は (2, 0) だけ
+    fn new_game(rule: GameRule) -> Game {
+        Game::new(board_file::parse("2\n#.M\n...\n..#").unwrap(), rule)
+    }
+
+    #[test]
+    fn test_open_until_cleared() {
+        let mut game = new_game(GameRule {
+            win_rule: WinRule::SafeTilesOpened,
+            ..Default::default()
+        });
+        let outcome = game.open(grid(1, 1));
+        assert_eq!(
+            outcome.move_tile,
+            Some(OnMoveTile::Open { target: grid(1, 1) })
+        );
+        assert_eq!(outcome.game_over, None);
+        assert!(!outcome.is_cleared);
+
+        let outcome = game.open(grid(0, 2));
+        assert!(matches!(
+            outcome.move_tile,
+            Some(OnMoveTile::OpenRegion { .. })
+        ));
+        assert!(outcome.is_cleared);
+        assert!(game.is_cleared());
+        assert!(game.open(grid(1, 0)).is_ignored());
+        assert!(game.flag(grid(2, 0)).is_ignored());
+    }
+
+    #[test]
+    fn test_open_mine_ends_the_game() {
+        let mut game = new_game(GameRule::default());
+        let outcome = game.open(grid(2, 0));
+        assert_eq!(
+            outcome.game_over,
+            Some(OnGameOver::Open { target: grid(2, 0) })
+        );
+        assert!(game.is_over());
+        assert!(game.open(grid(0, 2)).is_ignored());
+        assert!(game.flag(grid(1, 0)).is_ignored());
+        assert_eq!(game.undo(), None);
+        assert!(game.is_over());
+        assert_eq!(game.board().rescue_count(), 0);
+    }
+
+    #[test]
+    fn test_practice_undoes_the_losing_move() {
+        let mut game = new_game(GameRule {
+            practice: true,
+            ..Default::default()
+        });
+        game.open(grid(2, 0));
+        assert!(game.is_over());
+
+        let undo = game.undo().unwrap();
+        assert!(undo.is_rescue);
+        assert!(!game.is_over());
+        assert_eq!(game.board().rescue_count(), 1);
+
+        let outcome = game.redo();
+        assert!(outcome.game_over.is_some());
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn test_lives_defuse_mine() {
+        let mut game = new_game(GameRule {
+            lives: 2,
+            ..Default::default()
+        });
+        let outcome = game.open(grid(2, 0));
+        assert!(outcome.move_tile.is_some());
+        assert_eq!(outcome.game_over, None);
+        assert_eq!(game.board().count_defused_mines(), 1);
+    }
+
+    #[test]
+    fn test_flag_rules() {
+        let mut game = new_game(GameRule {
+            strict_flag: false,
+            win_rule: WinRule::MinesFlagged,
+            ..Default::default()
+        });
+        let outcome = game.flag(grid(1, 0));
+        assert_eq!(outcome.game_over, None);
+        assert!(!outcome.is_cleared);
+        game.flag(grid(1, 0));
+        game.flag(grid(1, 0));
+        assert!(game.flag(grid(2, 0)).is_cleared);
+
+        let mut strict = new_game(GameRule::default());
+        assert_eq!(
+            strict.flag(grid(1, 0)).game_over,
+            Some(OnGameOver::Flag { target: grid(1, 0) })
+        );
+    }
+
+    #[test]
+    fn test_flag_before_mines_are_placed_is_ignored() {
+        let source = BoardSource::Generate {
+            shape: BoardShape::hexagon(3),
+            mine_count: MineCount::Exact(3),
+            seed: Some("headless".to_string()),
+            first_click: FirstClickSafety::Tile,
+        };
+        let mut game = Game::from_source(&source, GameRule::default()).unwrap();
+        assert!(game.flag(grid(2, 2)).is_ignored());
+        assert!(game.open(grid(2, 2)).game_over.is_none());
+        assert_eq!(game.board().count_mines(), 3);
+    }
+}
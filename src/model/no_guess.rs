@@ -0,0 +1,159 @@
+use super::GameBoard;
+use crate::hexgrid::PointyHexGrid;
+use crate::solver::{self, SolveReport};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Layouts `FirstClickSafety::NoGuess` tries before it settles for the last one.
+pub const NO_GUESS_MAX_ATTEMPTS: usize = 100;
+
+/// Stops `FirstClickSafety::NoGuess` early on a machine too slow for
+/// `NO_GUESS_MAX_ATTEMPTS`. The board then no longer depends only on the seed.
+pub const NO_GUESS_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// How a `FirstClickSafety::NoGuess` board was generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationReport {
+    /// Layouts tried, including the one kept.
+    pub attempts: usize,
+    /// How the kept layout is cleared, or `None` if no attempt could be cleared by deduction.
+    pub solve: Option<SolveReport>,
+    /// `NO_GUESS_TIME_LIMIT` ran out before `NO_GUESS_MAX_ATTEMPTS`.
+    #[serde(default)]
+    pub is_out_of_time: bool,
+}
+
+impl fmt::Display for GenerationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.solve {
+            Some(solve) => write!(
+                f,
+                "no-guess board after {} attempts: {} rounds, hardest rule {:?}",
+                self.attempts, solve.rounds, solve.hardest
+            ),
+            None => write!(
+                f,
+                "gave up on a no-guess board after {} attempts{}",
+                self.attempts,
+                if self.is_out_of_time {
+                    " (out of time)"
+                } else {
+                    ""
+                }
+            ),
+        }
+    }
+}
+
+impl GameBoard {
+    /// Tries layouts until one can be cleared from `safe_grids[0]` by deduction alone,
+    /// keeping the last one if `NO_GUESS_MAX_ATTEMPTS` or `NO_GUESS_TIME_LIMIT` runs out first.
+    pub(super) fn place_no_guess_mines(&mut self, mine_count: usize, safe_grids: &[PointyHexGrid]) {
+        self.place_no_guess_mines_within(
+            mine_count,
+            safe_grids,
+            NO_GUESS_MAX_ATTEMPTS,
+            NO_GUESS_TIME_LIMIT,
+        );
+    }
+
+    fn place_no_guess_mines_within(
+        &mut self,
+        mine_count: usize,
+        safe_grids: &[PointyHexGrid],
+        max_attempts: usize,
+        time_limit: Duration,
+    ) {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let mut candidate = self.clone();
+            candidate.place_mines(mine_count, safe_grids, attempt);
+            candidate.open_region(safe_grids[0]);
+            let solve = solver::clear_without_guessing(&candidate);
+            attempt += 1;
+            // 試す回数で打ち切れば同じシードから同じ盤面になる．時間での打ち切りは保険
+            let is_out_of_time = started.elapsed() >= time_limit;
+            if solve.is_some() || attempt as usize >= max_attempts || is_out_of_time {
+                self.place_mines(mine_count, safe_grids, attempt - 1);
+                self.generation = Some(GenerationReport {
+                    attempts: attempt as usize,
+                    solve,
+                    is_out_of_time: solve.is_none() && is_out_of_time,
+                });
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BoardShape, FirstClickSafety, MineCount};
+
+    #[test]
+    fn test_no_guess_board_is_cleared_by_deduction() {
+        let first_open = PointyHexGrid { x: 3, y: 3 };
+        let mut game_board = GameBoard::generate(
+            BoardShape::hexagon(4),
+            MineCount::Exact(8),
+            "no guess",
+            FirstClickSafety::NoGuess,
+        );
+        assert_eq!(game_board.generation(), None);
+        game_board.place_pending_mines(first_open);
+        assert_eq!(game_board.count_mines(), 8);
+
+        let report = *game_board.generation().unwrap();
+        assert!(report.attempts >= 1);
+        assert!(report.solve.is_some());
+        game_board.open_region(first_open);
+        assert_eq!(solver::clear_without_guessing(&game_board), report.solve);
+    }
+
+    #[test]
+    fn test_no_guess_board_depends_only_on_seed() {
+        let first_open = PointyHexGrid { x: 3, y: 3 };
+        let generate = || {
+            let mut game_board = GameBoard::generate(
+                BoardShape::hexagon(4),
+                MineCount::Exact(8),
+                "same",
+                FirstClickSafety::NoGuess,
+            );
+            game_board.place_pending_mines(first_open);
+            game_board.to_board_text()
+        };
+        assert_eq!(generate(), generate());
+    }
+
+    // このシードでは何回試しても推理だけで解ける配置が出ない
+    fn crowded_report(max_attempts: usize, time_limit: Duration) -> GenerationReport {
+        let mut game_board = GameBoard::new(4);
+        game_board.seed = Some("crowded".to_string());
+        let first_open = PointyHexGrid { x: 3, y: 3 };
+        let mut safe_grids = vec![first_open];
+        safe_grids.extend(game_board.adjacent_grids(first_open));
+        game_board.place_no_guess_mines_within(15, &safe_grids, max_attempts, time_limit);
+        assert_eq!(game_board.count_mines(), 15);
+        *game_board.generation().unwrap()
+    }
+
+    #[test]
+    fn test_no_guess_gives_up_after_max_attempts() {
+        let report = crowded_report(20, Duration::MAX);
+        assert_eq!(report.attempts, 20);
+        assert_eq!(report.solve, None);
+        assert!(!report.is_out_of_time);
+    }
+
+    #[test]
+    fn test_no_guess_gives_up_when_out_of_time() {
+        let report = crowded_report(NO_GUESS_MAX_ATTEMPTS, Duration::ZERO);
+        assert_eq!(report.attempts, 1);
+        assert_eq!(report.solve, None);
+        assert!(report.is_out_of_time);
+    }
+}
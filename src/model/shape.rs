@@ -0,0 +1,393 @@
+use crate::hexgrid::{self, PointyHexGrid};
+use bevy::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Largest width, height or tiles per side a shape may have.
+const MAX_SIZE: usize = 1000;
+
+/// Which tiles make up a board. Tiles are stored in the `size()` rectangle of
+/// axial coordinates starting at (0, 0), and only the ones `contains` accepts exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedBoardShape")]
+pub enum BoardShape {
+    /// `tiles_per_side` tiles along each of the six edges.
+    Hexagon { tiles_per_side: usize },
+    /// `width` tiles along the x axis by `height` along the slanted y axis.
+    Parallelogram { width: usize, height: usize },
+    /// `tiles_per_side` tiles along each of the three edges.
+    Triangle { tiles_per_side: usize },
+    /// `height` rows of `width` tiles, every other row shifted by half a tile.
+    Rectangle { width: usize, height: usize },
+    /// A hexagon with a hexagonal hole of `hole_tiles_per_side` in the middle.
+    Ring {
+        tiles_per_side: usize,
+        hole_tiles_per_side: usize,
+    },
+    /// Any tiles in the `width` by `height` rectangle, `tiles[y * width + x]`.
+    Mask {
+        width: usize,
+        height: usize,
+        tiles: Vec<bool>,
+    },
+}
+
+/// `BoardShape` as written in a save, before `BoardShape::is_valid` has accepted it.
+#[derive(Deserialize)]
+enum UncheckedBoardShape {
+    Hexagon {
+        tiles_per_side: usize,
+    },
+    Parallelogram {
+        width: usize,
+        height: usize,
+    },
+    Triangle {
+        tiles_per_side: usize,
+    },
+    Rectangle {
+        width: usize,
+        height: usize,
+    },
+    Ring {
+        tiles_per_side: usize,
+        hole_tiles_per_side: usize,
+    },
+    Mask {
+        width: usize,
+        height: usize,
+        tiles: Vec<bool>,
+    },
+}
+
+impl TryFrom<UncheckedBoardShape> for BoardShape {
+    type Error = String;
+
+    fn try_from(unchecked: UncheckedBoardShape) -> Result<Self, Self::Error> {
+        let shape = match unchecked {
+            UncheckedBoardShape::Hexagon { tiles_per_side } => Self::Hexagon { tiles_per_side },
+            UncheckedBoardShape::Parallelogram { width, height } => {
+                Self::Parallelogram { width, height }
+            }
+            UncheckedBoardShape::Triangle { tiles_per_side } => Self::Triangle { tiles_per_side },
+            UncheckedBoardShape::Rectangle { width, height } => Self::Rectangle { width, height },
+            UncheckedBoardShape::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            } => Self::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            },
+            UncheckedBoardShape::Mask {
+                width,
+                height,
+                tiles,
+            } => Self::Mask {
+                width,
+                height,
+                tiles,
+            },
+        };
+        if shape.is_valid() {
+            Ok(shape)
+        } else {
+            Err(format!("invalid board shape {:?}", shape))
+        }
+    }
+}
+
+impl Default for BoardShape {
+    fn default() -> Self {
+        Self::hexagon(1)
+    }
+}
+
+impl fmt::Display for BoardShape {
+    /// Formats the shape as `parse` reads it, e.g. `ring 5 2`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hexagon { tiles_per_side } => write!(f, "hexagon {}", tiles_per_side),
+            Self::Parallelogram { width, height } => {
+                write!(f, "parallelogram {} {}", width, height)
+            }
+            Self::Triangle { tiles_per_side } => write!(f, "triangle {}", tiles_per_side),
+            Self::Rectangle { width, height } => write!(f, "rectangle {} {}", width, height),
+            Self::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            } => write!(f, "ring {} {}", tiles_per_side, hole_tiles_per_side),
+            Self::Mask { width, height, .. } => write!(f, "mask {} {}", width, height),
+        }
+    }
+}
+
+impl BoardShape {
+    pub fn hexagon(tiles_per_side: usize) -> Self {
+        Self::Hexagon { tiles_per_side }
+    }
+
+    /// Reads a shape written by `Display`. A mask comes back with every tile in it.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let kind = words.next()?;
+        // 大きすぎるマスクを作る前に弾く
+        let numbers = words
+            .map(|word| word.parse::<usize>().ok().filter(is_size))
+            .collect::<Option<Vec<_>>>()?;
+        let shape = match (kind, &numbers[..]) {
+            ("hexagon", &[tiles_per_side]) => Self::Hexagon { tiles_per_side },
+            ("parallelogram", &[width, height]) => Self::Parallelogram { width, height },
+            ("triangle", &[tiles_per_side]) => Self::Triangle { tiles_per_side },
+            ("rectangle", &[width, height]) => Self::Rectangle { width, height },
+            ("ring", &[tiles_per_side, hole_tiles_per_side]) => Self::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            },
+            ("mask", &[width, height]) => Self::Mask {
+                width,
+                height,
+                tiles: vec![true; width * height],
+            },
+            _ => return None,
+        };
+        Some(shape).filter(Self::is_valid)
+    }
+
+    /// Every size is between 1 and `MAX_SIZE`, a ring's hole is smaller than the ring and
+    /// a mask has one entry per cell. `size`, `contains` and `grids` may panic on any other shape.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Hexagon { tiles_per_side } | Self::Triangle { tiles_per_side } => {
+                is_size(tiles_per_side)
+            }
+            Self::Parallelogram { width, height } | Self::Rectangle { width, height } => {
+                is_size(width) && is_size(height)
+            }
+            Self::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            } => {
+                is_size(tiles_per_side)
+                    && is_size(hole_tiles_per_side)
+                    && hole_tiles_per_side < tiles_per_side
+            }
+            Self::Mask {
+                width,
+                height,
+                tiles,
+            } => is_size(width) && is_size(height) && tiles.len() == width * height,
+        }
+    }
+
+    /// Width and height of the rectangle of axial coordinates the tiles are stored in.
+    pub fn size(&self) -> (usize, usize) {
+        match *self {
+            Self::Hexagon { tiles_per_side } | Self::Ring { tiles_per_side, .. } => {
+                (2 * tiles_per_side - 1, 2 * tiles_per_side - 1)
+            }
+            Self::Triangle { tiles_per_side } => (tiles_per_side, tiles_per_side),
+            // 行が 2 つ下がるごとに x の始まりが 1 つ左にずれる
+            Self::Rectangle { width, height } => (width + (height - 1) / 2, height),
+            Self::Parallelogram { width, height } | Self::Mask { width, height, .. } => {
+                (width, height)
+            }
+        }
+    }
+
+    pub fn contains(&self, grid: PointyHexGrid) -> bool {
+        let (width, height) = self.size();
+        if grid.x < 0 || grid.y < 0 || grid.x >= width as i32 || grid.y >= height as i32 {
+            return false;
+        }
+        let (x, y) = (grid.x as usize, grid.y as usize);
+        match self {
+            Self::Hexagon { tiles_per_side } => {
+                distance_from_center(grid, *tiles_per_side) < *tiles_per_side
+            }
+            Self::Ring {
+                tiles_per_side,
+                hole_tiles_per_side,
+            } => (*hole_tiles_per_side..*tiles_per_side)
+                .contains(&distance_from_center(grid, *tiles_per_side)),
+            Self::Triangle { tiles_per_side } => x + y < *tiles_per_side,
+            Self::Rectangle { width, height } => {
+                let start = (height - 1) / 2 - y / 2;
+                (start..start + width).contains(&x)
+            }
+            Self::Parallelogram { .. } => true,
+            Self::Mask { tiles, .. } => tiles[y * width + x],
+        }
+    }
+
+    /// Iterates over every tile of the shape, row by row.
+    pub fn grids(&self) -> impl Iterator<Item = PointyHexGrid> + '_ {
+        let (width, height) = self.size();
+        (0..height as i32)
+            .flat_map(move |y| (0..width as i32).map(move |x| PointyHexGrid { x, y }))
+            .filter(|grid| self.contains(*grid))
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.grids().count()
+    }
+
+    /// Lowest and highest tile centres in cartesian coordinates, in units of the tile radius.
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let (min, max) = self.grids().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), grid| {
+                let point = hexgrid::pointy_hex_grid_to_cartesian(grid);
+                (min.min(point), max.max(point))
+            },
+        );
+        if min.x > max.x {
+            (Vec2::ZERO, Vec2::ZERO)
+        } else {
+            (min, max)
+        }
+    }
+
+    /// Middle of the tiles in cartesian coordinates, in units of the tile radius.
+    pub fn center(&self) -> Vec2 {
+        let (min, max) = self.bounds();
+        (min + max) / 2.0
+    }
+}
+
+fn is_size(n: &usize) -> bool {
+    (1..=MAX_SIZE).contains(n)
+}
+
+/// Hex distance from the middle tile of a hexagon with `tiles_per_side`.
+fn distance_from_center(grid: PointyHexGrid, tiles_per_side: usize) -> usize {
+    let center = tiles_per_side as i32 - 1;
+    let (dx, dy) = (grid.x - center, grid.y - center);
+    ((dx.abs() + dy.abs() + (dx + dy).abs()) / 2) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_count() {
+        assert_eq!(BoardShape::hexagon(4).tile_count(), 37);
+        assert_eq!(
+            BoardShape::Parallelogram {
+                width: 5,
+                height: 3
+            }
+            .tile_count(),
+            15
+        );
+        assert_eq!(BoardShape::Triangle { tiles_per_side: 4 }.tile_count(), 10);
+        assert_eq!(
+            BoardShape::Rectangle {
+                width: 6,
+                height: 5
+            }
+            .tile_count(),
+            30
+        );
+        assert_eq!(
+            BoardShape::Ring {
+                tiles_per_side: 4,
+                hole_tiles_per_side: 2
+            }
+            .tile_count(),
+            37 - 7
+        );
+        assert_eq!(
+            BoardShape::Mask {
+                width: 2,
+                height: 2,
+                tiles: vec![true, false, false, true]
+            }
+            .tile_count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_rectangle_rows_line_up() {
+        let shape = BoardShape::Rectangle {
+            width: 4,
+            height: 5,
+        };
+        // 偶数行は同じ位置から，奇数行は半タイル右から始まる
+        let row_starts = (0..5)
+            .map(|y| {
+                shape
+                    .grids()
+                    .filter(|grid| grid.y == y)
+                    .map(|grid| hexgrid::pointy_hex_grid_to_cartesian(grid).x)
+                    .fold(f32::MAX, f32::min)
+            })
+            .collect::<Vec<_>>();
+        let half = 3.0_f32.sqrt() / 2.0;
+        for (y, start) in row_starts.iter().enumerate() {
+            let expected = row_starts[0] + if y % 2 == 1 { half } else { 0.0 };
+            assert!((start - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_center() {
+        assert_eq!(
+            BoardShape::hexagon(3).center(),
+            hexgrid::pointy_hex_grid_to_cartesian(PointyHexGrid { x: 2, y: 2 })
+        );
+        let ring = BoardShape::Ring {
+            tiles_per_side: 3,
+            hole_tiles_per_side: 1,
+        };
+        assert!(!ring.contains(PointyHexGrid { x: 2, y: 2 }));
+        assert_eq!(ring.center(), BoardShape::hexagon(3).center());
+        assert_eq!(ring.bounds(), BoardShape::hexagon(3).bounds());
+        assert_eq!(BoardShape::hexagon(1).bounds(), (Vec2::ZERO, Vec2::ZERO));
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        for shape in [
+            BoardShape::hexagon(4),
+            BoardShape::Parallelogram {
+                width: 5,
+                height: 3,
+            },
+            BoardShape::Triangle { tiles_per_side: 6 },
+            BoardShape::Rectangle {
+                width: 7,
+                height: 4,
+            },
+            BoardShape::Ring {
+                tiles_per_side: 5,
+                hole_tiles_per_side: 2,
+            },
+        ] {
+            assert_eq!(BoardShape::parse(&shape.to_string()), Some(shape));
+        }
+        assert_eq!(BoardShape::parse("ring 3 3"), None);
+        assert_eq!(BoardShape::parse("hexagon 0"), None);
+        assert_eq!(BoardShape::parse("circle 4"), None);
+        assert_eq!(BoardShape::parse("rectangle 4"), None);
+    }
+
+    #[test]
+    fn test_deserialize_rejects_invalid_shapes() {
+        let shape = BoardShape::Ring {
+            tiles_per_side: 5,
+            hole_tiles_per_side: 2,
+        };
+        let text = ron::to_string(&shape).unwrap();
+        assert_eq!(ron::from_str::<BoardShape>(&text).unwrap(), shape);
+        for text in [
+            "Hexagon(tiles_per_side: 0)",
+            "Ring(tiles_per_side: 0, hole_tiles_per_side: 0)",
+            "Rectangle(width: 3, height: 0)",
+            "Mask(width: 2, height: 2, tiles: [true])",
+        ] {
+            assert!(ron::from_str::<BoardShape>(text).is_err(), "{}", text);
+        }
+    }
+}
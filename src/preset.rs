@@ -0,0 +1,380 @@
+use crate::events::*;
+use crate::level::Levels;
+use crate::model::{BoardShape, BoardSource, FirstClickSafety, GameBoard, GameRule};
+use bevy::prelude::*;
+use std::fmt;
+
+pub const MIN_TILES_PER_SIDE: usize = 2;
+pub const MAX_TILES_PER_SIDE: usize = 12;
+pub const MAX_LIVES: usize = 5;
+
+/// Tiles the first open may keep free of mines: the tile itself and up to six neighbours.
+/// Fewer on the edge of a board, so this is an upper bound for any shape.
+pub const SAFE_OPENING_TILES: usize = 7;
+
+pub struct PresetPlugin;
+
+impl Plugin for PresetPlugin {
+    fn build(&self, app: &mut App) {
+        let is_no_guess = matches!(
+            app.world.resource::<BoardSource>(),
+            BoardSource::Generate {
+                first_click: FirstClickSafety::NoGuess,
+                ..
+            }
+        );
+        let rule = app.world.resource::<GameRule>().clone();
+        app.insert_resource(DifficultyMenu {
+            is_no_guess,
+            rule,
+            ..default()
+        })
+        .add_system(on_select_difficulty_system);
+    }
+}
+
+/// A named hexagonal board size and mine density.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub tiles_per_side: usize,
+    pub density: f32,
+}
+
+pub const PRESETS: [Preset; 3] = [
+    Preset {
+        name: "Beginner",
+        tiles_per_side: 4,
+        density: 0.12,
+    },
+    Preset {
+        name: "Intermediate",
+        tiles_per_side: 6,
+        density: 0.16,
+    },
+    Preset {
+        name: "Expert",
+        tiles_per_side: 8,
+        density: 0.2,
+    },
+];
+
+/// Shapes the custom dialog can build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Hexagon,
+    Parallelogram,
+    Triangle,
+    Rectangle,
+    Ring,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 5] = [
+        Self::Hexagon,
+        Self::Parallelogram,
+        Self::Triangle,
+        Self::Rectangle,
+        Self::Ring,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hexagon => "hexagon",
+            Self::Parallelogram => "parallelogram",
+            Self::Triangle => "triangle",
+            Self::Rectangle => "rectangle",
+            Self::Ring => "ring",
+        }
+    }
+}
+
+/// Board chosen in the custom dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomBoard {
+    pub kind: ShapeKind,
+    /// Tiles per side, or the width of a parallelogram or rectangle.
+    pub size: usize,
+    /// Height of a parallelogram or rectangle, or tiles per side of a ring's hole.
+    pub height: usize,
+    pub mine_count: usize,
+}
+
+impl Default for CustomBoard {
+    fn default() -> Self {
+        Self {
+            kind: ShapeKind::Hexagon,
+            size: 5,
+            height: 3,
+            mine_count: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomBoardError {
+    TooSmall,
+    TooLarge,
+    HoleTooLarge,
+    NoMines,
+    TooManyMines { max: usize },
+}
+
+impl fmt::Display for CustomBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooSmall => write!(f, "size must be at least {}", MIN_TILES_PER_SIDE),
+            Self::TooLarge => write!(f, "size must be at most {}", MAX_TILES_PER_SIDE),
+            Self::HoleTooLarge => write!(f, "the hole must be smaller than the ring"),
+            Self::NoMines => write!(f, "place at least 1 mine"),
+            Self::TooManyMines { max } => write!(
+                f,
+                "at most {} mines fit and leave room for a safe opening",
+                max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CustomBoardError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomField {
+    Shape,
+    Size,
+    Height,
+    MineCount,
+}
+
+impl CustomBoard {
+    pub fn shape(&self) -> BoardShape {
+        let (size, height) = (self.size, self.height);
+        match self.kind {
+            ShapeKind::Hexagon => BoardShape::hexagon(size),
+            ShapeKind::Parallelogram => BoardShape::Parallelogram {
+                width: size,
+                height,
+            },
+            ShapeKind::Triangle => BoardShape::Triangle {
+                tiles_per_side: size,
+            },
+            ShapeKind::Rectangle => BoardShape::Rectangle {
+                width: size,
+                height,
+            },
+            ShapeKind::Ring => BoardShape::Ring {
+                tiles_per_side: size,
+                hole_tiles_per_side: height,
+            },
+        }
+    }
+
+    /// Fields the dialog shows for the current shape, top to bottom.
+    pub fn fields(&self) -> Vec<CustomField> {
+        match self.kind {
+            ShapeKind::Hexagon | ShapeKind::Triangle => {
+                vec![
+                    CustomField::Shape,
+                    CustomField::Size,
+                    CustomField::MineCount,
+                ]
+            }
+            _ => vec![
+                CustomField::Shape,
+                CustomField::Size,
+                CustomField::Height,
+                CustomField::MineCount,
+            ],
+        }
+    }
+
+    pub fn label(&self, field: CustomField) -> &'static str {
+        match (field, self.kind) {
+            (CustomField::Shape, _) => "shape",
+            (CustomField::Size, ShapeKind::Parallelogram | ShapeKind::Rectangle) => "width",
+            (CustomField::Size, _) => "size",
+            (CustomField::Height, ShapeKind::Ring) => "hole",
+            (CustomField::Height, _) => "height",
+            (CustomField::MineCount, _) => "mines",
+        }
+    }
+
+    /// Moves `field` one step up or down, keeping sizes in range.
+    pub fn adjust(&mut self, field: CustomField, is_up: bool) {
+        let step = |value: usize, min: usize| {
+            if is_up {
+                (value + 1).min(MAX_TILES_PER_SIDE)
+            } else {
+                value.saturating_sub(1).max(min)
+            }
+        };
+        match field {
+            CustomField::Shape => {
+                let len = ShapeKind::ALL.len();
+                let index = ShapeKind::ALL.iter().position(|kind| *kind == self.kind);
+                let index = index.unwrap_or_default() + if is_up { 1 } else { len - 1 };
+                self.kind = ShapeKind::ALL[index % len];
+            }
+            CustomField::Size => self.size = step(self.size, MIN_TILES_PER_SIDE),
+            // リングの穴は 1 枚から
+            CustomField::Height if self.kind == ShapeKind::Ring => {
+                self.height = step(self.height, 1)
+            }
+            CustomField::Height => self.height = step(self.height, MIN_TILES_PER_SIDE),
+            CustomField::MineCount if is_up => self.mine_count += 1,
+            CustomField::MineCount => self.mine_count = self.mine_count.saturating_sub(1),
+        }
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.shape().tile_count()
+    }
+
+    pub fn max_mine_count(&self) -> usize {
+        self.tile_count().saturating_sub(SAFE_OPENING_TILES)
+    }
+
+    pub fn check(&self) -> Result<(), CustomBoardError> {
+        let mut sizes = vec![self.size];
+        if matches!(self.kind, ShapeKind::Parallelogram | ShapeKind::Rectangle) {
+            sizes.push(self.height);
+        }
+        if sizes.iter().any(|size| *size < MIN_TILES_PER_SIDE) {
+            Err(CustomBoardError::TooSmall)
+        } else if sizes.iter().any(|size| *size > MAX_TILES_PER_SIDE) {
+            Err(CustomBoardError::TooLarge)
+        } else if self.kind == ShapeKind::Ring && !(1..self.size).contains(&self.height) {
+            Err(CustomBoardError::HoleTooLarge)
+        } else if self.mine_count == 0 {
+            Err(CustomBoardError::NoMines)
+        } else if self.mine_count > self.max_mine_count() {
+            Err(CustomBoardError::TooManyMines {
+                max: self.max_mine_count(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct DifficultyMenu {
+    /// Row highlighted: an index into `PRESETS`, or `PRESETS.len()` for the custom board.
+    pub cursor: usize,
+    pub custom: CustomBoard,
+    /// Field being edited while the custom dialog is open.
+    pub custom_field: Option<CustomField>,
+    /// Boards started from the menu can be cleared without guessing.
+    pub is_no_guess: bool,
+    /// Rule for games started from the menu.
+    pub rule: GameRule,
+}
+
+fn on_select_difficulty_system(
+    mut reader: EventReader<OnSelectDifficulty>,
+    mut levels: ResMut<Levels>,
+    mut game_board: ResMut<GameBoard>,
+    mut board_source: ResMut<BoardSource>,
+    mut game_rule: ResMut<GameRule>,
+    mut writer: EventWriter<OnResetBoard>,
+) {
+    for event in reader.iter() {
+        // 推理だけで解ける盤面にしないなら，最初のクリックの扱いは今の設定を引き継ぐ
+        let first_click = match *board_source {
+            _ if event.is_no_guess => FirstClickSafety::NoGuess,
+            BoardSource::Generate {
+                first_click: FirstClickSafety::NoGuess,
+                ..
+            }
+            | BoardSource::Fixed(_) => FirstClickSafety::Opening,
+            BoardSource::Generate { first_click, .. } => first_click,
+        };
+        let new_source = BoardSource::Generate {
+            shape: event.shape.clone(),
+            mine_count: event.mine_count,
+            seed: None,
+            first_click,
+        };
+        match new_source.build() {
+            Ok(new_board) => {
+                if let Some(seed) = new_board.seed() {
+                    info!("board seed: {}", seed);
+                }
+                *board_source = new_source;
+                *game_board = new_board;
+                *game_rule = event.rule.clone();
+                levels.current = None;
+                writer.send(OnResetBoard);
+            }
+            Err(err) => error!("failed to build a new game board: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::MineCount;
+
+    #[test]
+    fn test_presets_are_valid_boards() {
+        for preset in PRESETS {
+            let custom = CustomBoard {
+                size: preset.tiles_per_side,
+                mine_count: MineCount::Density(preset.density)
+                    .resolve(BoardShape::hexagon(preset.tiles_per_side).tile_count()),
+                ..default()
+            };
+            assert_eq!(custom.check(), Ok(()), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn test_check_custom_board() {
+        let board = |size, mine_count| CustomBoard {
+            size,
+            mine_count,
+            ..default()
+        };
+        assert_eq!(board(3, 12).tile_count(), 19);
+        assert_eq!(board(3, 12).check(), Ok(()));
+        assert_eq!(
+            board(3, 13).check(),
+            Err(CustomBoardError::TooManyMines { max: 12 })
+        );
+        assert_eq!(board(3, 0).check(), Err(CustomBoardError::NoMines));
+        assert_eq!(board(1, 1).check(), Err(CustomBoardError::TooSmall));
+        assert_eq!(
+            board(MAX_TILES_PER_SIDE + 1, 1).check(),
+            Err(CustomBoardError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_custom_shapes() {
+        let mut custom = CustomBoard {
+            size: 4,
+            height: 3,
+            mine_count: 3,
+            ..default()
+        };
+        let mut tile_counts = vec![];
+        for _ in ShapeKind::ALL {
+            assert_eq!(custom.check(), Ok(()), "{:?}", custom.kind);
+            assert_eq!(custom.tile_count(), custom.shape().tile_count());
+            tile_counts.push(custom.tile_count());
+            custom.adjust(CustomField::Shape, true);
+        }
+        assert_eq!(custom.kind, ShapeKind::Hexagon);
+        // 六角形，平行四辺形，三角形，長方形，リング
+        assert_eq!(tile_counts, vec![37, 12, 10, 12, 37 - 19]);
+
+        custom.adjust(CustomField::Shape, false);
+        assert_eq!(custom.kind, ShapeKind::Ring);
+        custom.height = 4;
+        assert_eq!(custom.check(), Err(CustomBoardError::HoleTooLarge));
+        custom.kind = ShapeKind::Rectangle;
+        custom.height = 1;
+        assert_eq!(custom.check(), Err(CustomBoardError::TooSmall));
+    }
+}
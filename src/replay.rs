@@ -0,0 +1,464 @@
+use crate::events::*;
+use crate::hexgrid::PointyHexGrid;
+use crate::model::{BoardSource, GameBoard, GameRule};
+use crate::save::SaveError;
+use crate::scene::GameScene;
+use crate::view::{tilemap_point, Tilemap};
+use crate::{Config, CursorWorldPosition};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const REPLAY_PATH: &str = "replay.ron";
+
+const MIN_REPLAY_SPEED: f32 = 0.25;
+const MAX_REPLAY_SPEED: f32 = 8.0;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let recorder = ReplayRecorder::new(
+            app.world.resource::<BoardSource>(),
+            app.world.resource::<GameBoard>(),
+            app.world.resource::<GameRule>(),
+        );
+        app.insert_resource(recorder)
+            .init_resource::<ReplayPlayer>()
+            .add_event::<OnPlayerInput>()
+            .add_system(tick_recorder_system)
+            .add_system(record_input_system)
+            .add_system(on_reset_board_system)
+            .add_system(save_replay_system)
+            .add_system(on_start_replay_system)
+            .add_system(play_replay_system)
+            .add_system(on_stop_replay_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Open { target: PointyHexGrid },
+    Flag { target: PointyHexGrid },
+    Chord { target: PointyHexGrid },
+    Undo,
+    Redo,
+}
+
+/// Sent with every `OnTry*` event the player makes, so that the replay keeps their order.
+#[derive(Debug, Clone, Copy)]
+pub struct OnPlayerInput {
+    pub action: ReplayAction,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplayInput {
+    /// Seconds since the recording started.
+    pub time: f32,
+    /// Mouse position in tile units, relative to the tilemap.
+    pub cursor: [f32; 2],
+    pub action: ReplayAction,
+}
+
+/// A recorded game: the board it started from and every player input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub board_source: BoardSource,
+    /// The board when the recording started, including the seed of a generated board.
+    pub game_board: GameBoard,
+    pub game_rule: GameRule,
+    pub inputs: Vec<ReplayInput>,
+}
+
+impl Replay {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, default()).map_err(SaveError::Serialize)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        ron::from_str(text).map_err(SaveError::Deserialize)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::from_ron(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        Ok(fs::write(path, self.to_ron()?)?)
+    }
+}
+
+/// Records the inputs of the game being played.
+#[derive(Resource, Debug)]
+pub struct ReplayRecorder {
+    pub replay: Replay,
+    pub elapsed: f32,
+}
+
+impl ReplayRecorder {
+    pub fn new(board_source: &BoardSource, game_board: &GameBoard, game_rule: &GameRule) -> Self {
+        Self {
+            replay: Replay {
+                board_source: board_source.clone(),
+                game_board: game_board.clone(),
+                game_rule: game_rule.clone(),
+                inputs: vec![],
+            },
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, cursor: Vec2, action: ReplayAction) {
+        self.replay.inputs.push(ReplayInput {
+            time: self.elapsed,
+            cursor: cursor.to_array(),
+            action,
+        });
+    }
+}
+
+/// Playback state of the replay being watched, if any.
+#[derive(Resource, Debug)]
+pub struct ReplayPlayer {
+    pub replay: Option<Replay>,
+    /// Seconds into the replay.
+    pub time: f32,
+    /// Index of the next input to play.
+    pub next: usize,
+    pub is_paused: bool,
+    pub speed: f32,
+    /// The player's own rule, restored when the playback stops.
+    previous_rule: Option<GameRule>,
+}
+
+impl Default for ReplayPlayer {
+    fn default() -> Self {
+        Self {
+            replay: None,
+            time: 0.0,
+            next: 0,
+            is_paused: false,
+            speed: 1.0,
+            previous_rule: None,
+        }
+    }
+}
+
+impl ReplayPlayer {
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_REPLAY_SPEED);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_REPLAY_SPEED);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match &self.replay {
+            Some(replay) => self.next >= replay.inputs.len(),
+            None => true,
+        }
+    }
+
+    /// Advances the clock by `delta` real seconds and returns the next input
+    /// if it is due. At most one input is returned per call so that inputs
+    /// reach the model in the recorded order.
+    pub fn advance(&mut self, delta: f32) -> Option<ReplayAction> {
+        let replay = self.replay.as_ref()?;
+        if !self.is_paused {
+            self.time += delta * self.speed;
+        }
+        let input = replay.inputs.get(self.next)?;
+        if input.time > self.time {
+            return None;
+        }
+        self.next += 1;
+        Some(input.action)
+    }
+
+    /// Where the ghost cursor is, gliding from the last played input to the
+    /// next one.
+    pub fn cursor(&self) -> Option<Vec2> {
+        let inputs = &self.replay.as_ref()?.inputs;
+        let next = inputs.get(self.next);
+        let last = self.next.checked_sub(1).and_then(|i| inputs.get(i));
+        match (last, next) {
+            (Some(last), Some(next)) if next.time > last.time => {
+                let t = ((self.time - last.time) / (next.time - last.time)).clamp(0.0, 1.0);
+                Some(Vec2::from(last.cursor).lerp(Vec2::from(next.cursor), t))
+            }
+            (Some(input), _) | (None, Some(input)) => Some(Vec2::from(input.cursor)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn tick_recorder_system(
+    time: Res<Time>,
+    game_scene: Res<GameScene>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if !matches!(*game_scene, GameScene::Replay) {
+        recorder.elapsed += time.delta_seconds();
+    }
+}
+
+fn record_input_system(
+    mut reader: EventReader<OnPlayerInput>,
+    mut on_stop_replay_reader: EventReader<OnStopReplay>,
+    game_scene: Res<GameScene>,
+    mut recorder: ResMut<ReplayRecorder>,
+    cursor_world_position: Res<CursorWorldPosition>,
+    tilemap_query: Query<&Transform, With<Tilemap>>,
+    config: Res<Config>,
+) {
+    // リプレイを止めたフレームの操作は，再生中のものか止めた後のものか区別できないので捨てる
+    if on_stop_replay_reader.iter().last().is_some() || matches!(*game_scene, GameScene::Replay) {
+        reader.clear();
+        return;
+    }
+    let cursor = tilemap_point(
+        tilemap_query.single(),
+        cursor_world_position.position,
+        &config,
+    );
+    for event in reader.iter() {
+        recorder.record(cursor, event.action);
+    }
+}
+
+fn on_reset_board_system(
+    mut reader: EventReader<OnResetBoard>,
+    game_scene: Res<GameScene>,
+    board_source: Res<BoardSource>,
+    game_board: Res<GameBoard>,
+    game_rule: Res<GameRule>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if reader.iter().last().is_some() && !matches!(*game_scene, GameScene::Replay) {
+        *recorder = ReplayRecorder::new(&board_source, &game_board, &game_rule);
+    }
+}
+
+fn save_replay_system(
+    mut on_game_over_reader: EventReader<OnGameOver>,
+    mut on_game_clear_reader: EventReader<OnGameClear>,
+    mut on_quit_game_reader: EventReader<OnQuitGame>,
+    game_scene: Res<GameScene>,
+    recorder: Res<ReplayRecorder>,
+) {
+    let is_ended = on_game_over_reader.iter().last().is_some()
+        | on_game_clear_reader.iter().last().is_some()
+        | on_quit_game_reader.iter().last().is_some();
+    if !is_ended || matches!(*game_scene, GameScene::Replay) || recorder.replay.inputs.is_empty() {
+        return;
+    }
+    match recorder.replay.save(REPLAY_PATH) {
+        Ok(()) => info!("replay saved to {}", REPLAY_PATH),
+        Err(err) => error!("failed to save the replay: {}", err),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_start_replay_system(
+    mut reader: EventReader<OnStartReplay>,
+    mut game_scene: ResMut<GameScene>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut board_source: ResMut<BoardSource>,
+    mut game_board: ResMut<GameBoard>,
+    mut game_rule: ResMut<GameRule>,
+    mut writer: EventWriter<OnResetBoard>,
+) {
+    if reader.iter().last().is_none() {
+        return;
+    }
+    let replay = match Replay::load(REPLAY_PATH) {
+        Ok(replay) => replay,
+        Err(err) => {
+            error!("failed to load the replay: {}", err);
+            return;
+        }
+    };
+    *board_source = replay.board_source.clone();
+    *game_board = replay.game_board.clone();
+    let previous_rule = std::mem::replace(&mut *game_rule, replay.game_rule.clone());
+    *replay_player = ReplayPlayer {
+        replay: Some(replay),
+        previous_rule: Some(previous_rule),
+        ..default()
+    };
+    *game_scene = GameScene::Replay;
+    writer.send(OnResetBoard);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_replay_system(
+    time: Res<Time>,
+    game_scene: Res<GameScene>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut on_try_open_tile_writer: EventWriter<OnTryOpenTile>,
+    mut on_try_flag_tile_writer: EventWriter<OnTryFlagTile>,
+    mut on_try_chord_tile_writer: EventWriter<OnTryChordTile>,
+    mut on_try_undo_writer: EventWriter<OnTryUndo>,
+    mut on_try_redo_writer: EventWriter<OnTryRedo>,
+) {
+    if !matches!(*game_scene, GameScene::Replay) {
+        return;
+    }
+    match replay_player.advance(time.delta_seconds()) {
+        Some(ReplayAction::Open { target }) => {
+            on_try_open_tile_writer.send(OnTryOpenTile { target })
+        }
+        Some(ReplayAction::Flag { target }) => {
+            on_try_flag_tile_writer.send(OnTryFlagTile { target })
+        }
+        Some(ReplayAction::Chord { target }) => {
+            on_try_chord_tile_writer.send(OnTryChordTile { target })
+        }
+        Some(ReplayAction::Undo) => on_try_undo_writer.send(OnTryUndo),
+        Some(ReplayAction::Redo) => on_try_redo_writer.send(OnTryRedo),
+        None => {}
+    }
+}
+
+fn on_stop_replay_system(
+    mut reader: EventReader<OnStopReplay>,
+    mut game_scene: ResMut<GameScene>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut game_rule: ResMut<GameRule>,
+    game_board: Res<GameBoard>,
+) {
+    if reader.iter().last().is_none() {
+        return;
+    }
+    let player = std::mem::take(&mut *replay_player);
+    if let Some(mut replay) = player.replay {
+        // 途中で止めたらそこから先の操作は捨てて，続きを自分で遊べるようにする
+        replay.inputs.truncate(player.next);
+        recorder.replay = replay;
+        recorder.elapsed = player.time;
+    }
+    if let Some(previous_rule) = player.previous_rule {
+        *game_rule = previous_rule;
+    }
+    *game_scene = if game_board.is_game_over() {
+        GameScene::Over
+    } else if game_board.is_cleared(game_board.win_rule(game_rule.win_rule)) {
+        GameScene::Clear
+    } else {
+        GameScene::InGame
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BoardShape;
+
+    fn replay(times: &[f32]) -> Replay {
+        Replay {
+            board_source: BoardSource::default(),
+            game_board: GameBoard::new(2),
+            game_rule: GameRule::default(),
+            inputs: times
+                .iter()
+                .enumerate()
+                .map(|(i, &time)| ReplayInput {
+                    time,
+                    cursor: [i as f32, 0.0],
+                    action: ReplayAction::Open {
+                        target: PointyHexGrid { x: i as i32, y: 1 },
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_advance_plays_inputs_in_order() {
+        let mut player = ReplayPlayer {
+            replay: Some(replay(&[0.5, 0.5, 2.0])),
+            ..default()
+        };
+        assert_eq!(player.advance(0.25), None);
+        assert!(matches!(
+            player.advance(0.25),
+            Some(ReplayAction::Open { target }) if target.x == 0
+        ));
+        // 同じ時刻の操作も 1 フレームに 1 つずつ
+        assert!(matches!(
+            player.advance(0.0),
+            Some(ReplayAction::Open { target }) if target.x == 1
+        ));
+        assert_eq!(player.advance(0.0), None);
+
+        player.is_paused = true;
+        assert_eq!(player.advance(10.0), None);
+        player.is_paused = false;
+        player.speed_up();
+        assert_eq!(player.speed, 2.0);
+        assert!(player.advance(0.75).is_some());
+        assert!(player.is_finished());
+        assert_eq!(player.advance(1.0), None);
+    }
+
+    #[test]
+    fn test_speed_is_clamped() {
+        let mut player = ReplayPlayer::default();
+        for _ in 0..10 {
+            player.speed_up();
+        }
+        assert_eq!(player.speed, MAX_REPLAY_SPEED);
+        for _ in 0..10 {
+            player.slow_down();
+        }
+        assert_eq!(player.speed, MIN_REPLAY_SPEED);
+    }
+
+    #[test]
+    fn test_ghost_cursor_glides_between_inputs() {
+        let mut player = ReplayPlayer {
+            replay: Some(replay(&[1.0, 3.0])),
+            ..default()
+        };
+        assert_eq!(player.cursor(), Some(Vec2::new(0.0, 0.0)));
+        player.advance(1.0);
+        player.advance(1.0);
+        assert_eq!(player.cursor(), Some(Vec2::new(0.5, 0.0)));
+        player.advance(1.0);
+        assert_eq!(player.cursor(), Some(Vec2::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_replay_round_trip() {
+        let mut recorder = ReplayRecorder::new(
+            &BoardSource::default(),
+            &GameBoard::new(3),
+            &GameRule {
+                lives: 3,
+                strict_flag: false,
+                ..default()
+            },
+        );
+        recorder.elapsed = 1.5;
+        recorder.record(
+            Vec2::new(2.0, 2.0),
+            ReplayAction::Flag {
+                target: PointyHexGrid { x: 2, y: 2 },
+            },
+        );
+        recorder.elapsed = 2.0;
+        recorder.record(Vec2::ZERO, ReplayAction::Undo);
+
+        let replay = Replay::from_ron(&recorder.replay.to_ron().unwrap()).unwrap();
+        assert_eq!(replay.game_board.shape(), &BoardShape::hexagon(3));
+        assert_eq!(replay.game_rule.lives, 3);
+        assert!(!replay.game_rule.strict_flag);
+        assert_eq!(replay.inputs.len(), 2);
+        assert_eq!(replay.inputs[0].time, 1.5);
+        assert_eq!(replay.inputs[0].cursor, [2.0, 2.0]);
+        assert_eq!(replay.inputs[1].action, ReplayAction::Undo);
+    }
+}
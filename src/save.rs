@@ -0,0 +1,209 @@
+use crate::events::*;
+use crate::model::{BoardSource, GameBoard, GameRule};
+use crate::scene::GameScene;
+use crate::stats::GameStats;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const SAVE_PATH: &str = "save.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        match SaveData::load(SAVE_PATH) {
+            Ok(Some(save_data)) => {
+                info!("resuming the game saved in {}", SAVE_PATH);
+                app.insert_resource(save_data.board_source)
+                    .insert_resource(save_data.game_board)
+                    .insert_resource(save_data.game_rule)
+                    .insert_resource(save_data.game_stats);
+            }
+            Ok(None) => {}
+            Err(err) => error!("failed to load the saved game, starting a new one: {}", err),
+        }
+        app.add_system(on_quit_game_system);
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Serialize(err) => write!(f, "{}", err),
+            Self::Deserialize(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Everything needed to resume an unfinished game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    /// Where a retry with a new board draws it from.
+    pub board_source: BoardSource,
+    /// Tile states and move history, including the seed of a generated board.
+    pub game_board: GameBoard,
+    /// Saves from before the rule was kept resume with the default one.
+    #[serde(default)]
+    pub game_rule: GameRule,
+    pub game_stats: GameStats,
+}
+
+impl SaveData {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, default()).map_err(SaveError::Serialize)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        ron::from_str(text).map_err(SaveError::Deserialize)
+    }
+
+    /// Reads the save at `path`. A missing file is not an error.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, SaveError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::from_ron(&text).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        Ok(fs::write(path, self.to_ron()?)?)
+    }
+
+    /// Deletes the save at `path`, if any.
+    pub fn remove(path: impl AsRef<Path>) -> Result<(), SaveError> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn on_quit_game_system(
+    mut reader: EventReader<OnQuitGame>,
+    game_scene: Res<GameScene>,
+    board_source: Res<BoardSource>,
+    game_board: Res<GameBoard>,
+    game_rule: Res<GameRule>,
+    game_stats: Res<GameStats>,
+    mut writer: EventWriter<AppExit>,
+) {
+    if reader.iter().last().is_none() {
+        return;
+    }
+    // 終わったゲームとまだ始まっていないゲームは残さない
+    // リプレイは終わったゲームからしか始められないので，再生中の盤面も残さない
+    let result = match *game_scene {
+        GameScene::Over | GameScene::Clear | GameScene::Replay => SaveData::remove(SAVE_PATH),
+        _ if !game_stats.is_started => SaveData::remove(SAVE_PATH),
+        _ => SaveData {
+            board_source: board_source.clone(),
+            game_board: game_board.clone(),
+            game_rule: game_rule.clone(),
+            game_stats: game_stats.clone(),
+        }
+        .save(SAVE_PATH),
+    };
+    if let Err(err) = result {
+        error!("failed to save the game: {}", err);
+    }
+    writer.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::OnMoveTile;
+    use crate::hexgrid::PointyHexGrid;
+    use crate::model::{BoardShape, FirstClickSafety, MineCount, WinRule};
+
+    #[test]
+    fn test_save_data_round_trip() {
+        let board_source = BoardSource::Generate {
+            shape: BoardShape::hexagon(4),
+            mine_count: MineCount::Exact(6),
+            seed: Some("resume".to_string()),
+            first_click: FirstClickSafety::Tile,
+        };
+        let mut game_board = board_source.build().unwrap();
+        let target = PointyHexGrid { x: 3, y: 3 };
+        game_board.place_pending_mines(target);
+        let targets = game_board.open_region(target);
+        game_board.push_move(OnMoveTile::OpenRegion { targets });
+        let closed = game_board
+            .grids()
+            .find(|grid| !game_board.get(*grid).unwrap().is_open())
+            .unwrap();
+        let flag = game_board.cycle_marker(closed).unwrap();
+        game_board.push_move(flag);
+        let game_stats = GameStats {
+            elapsed: 12.5,
+            is_started: true,
+            opens: 1,
+            flags: 1,
+            ..default()
+        };
+        let game_rule = GameRule {
+            practice: true,
+            lives: 3,
+            strict_flag: false,
+            win_rule: WinRule::MinesFlagged,
+        };
+        let save_data = SaveData {
+            board_source,
+            game_board,
+            game_rule,
+            game_stats,
+        };
+
+        let mut loaded = SaveData::from_ron(&save_data.to_ron().unwrap()).unwrap();
+        assert!(matches!(
+            loaded.board_source,
+            BoardSource::Generate { ref seed, .. } if seed.as_deref() == Some("resume")
+        ));
+        assert_eq!(loaded.game_board.seed(), Some("resume"));
+        assert_eq!(
+            loaded.game_board.to_board_text(),
+            save_data.game_board.to_board_text()
+        );
+        assert!(loaded.game_rule.practice);
+        assert_eq!(loaded.game_rule.lives, 3);
+        assert!(!loaded.game_rule.strict_flag);
+        assert_eq!(loaded.game_rule.win_rule, WinRule::MinesFlagged);
+        assert_eq!(loaded.game_stats.elapsed, 12.5);
+        assert_eq!(loaded.game_stats.clicks(), 2);
+
+        // 手の履歴も戻るので，再開後に undo できる
+        assert!(loaded.game_board.undo().is_some());
+        assert!(loaded.game_board.undo().is_some());
+        assert!(loaded.game_board.undo().is_none());
+        assert_eq!(loaded.game_board.count_open_tile(), 0);
+    }
+
+    #[test]
+    fn test_load_missing_save() {
+        assert!(SaveData::load("no/such/save.ron").unwrap().is_none());
+        assert!(SaveData::remove("no/such/save.ron").is_ok());
+    }
+}
@@ -7,7 +7,10 @@ impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(GameScene::InGame)
             .add_system(check_game_over_system)
-            .add_system(check_game_clear_system);
+            .add_system(check_game_clear_system)
+            .add_system(check_rescue_system)
+            .add_system(check_select_level_system)
+            .add_system(check_select_difficulty_system);
     }
 }
 
@@ -16,16 +19,49 @@ pub enum GameScene {
     Over,
     Clear,
     InGame,
+    LevelSelect,
+    /// Choosing a difficulty preset or a custom board.
+    DifficultyMenu,
+    /// Playing back a replay. Player input on the board is ignored.
+    Replay,
 }
 
+// リプレイ中はゲームオーバーやクリアになってもリプレイを続ける
 fn check_game_over_system(mut reader: EventReader<OnGameOver>, mut scene: ResMut<GameScene>) {
     for _ in reader.iter() {
-        *scene = GameScene::Over;
+        if !matches!(*scene, GameScene::Replay) {
+            *scene = GameScene::Over;
+        }
     }
 }
 
 fn check_game_clear_system(mut reader: EventReader<OnGameClear>, mut scene: ResMut<GameScene>) {
     for _ in reader.iter() {
-        *scene = GameScene::Clear;
+        if !matches!(*scene, GameScene::Replay) {
+            *scene = GameScene::Clear;
+        }
+    }
+}
+
+fn check_rescue_system(mut reader: EventReader<OnRescue>, mut scene: ResMut<GameScene>) {
+    for _ in reader.iter() {
+        if !matches!(*scene, GameScene::Replay) {
+            *scene = GameScene::InGame;
+        }
+    }
+}
+
+fn check_select_level_system(mut reader: EventReader<OnSelectLevel>, mut scene: ResMut<GameScene>) {
+    for _ in reader.iter() {
+        *scene = GameScene::InGame;
+    }
+}
+
+fn check_select_difficulty_system(
+    mut reader: EventReader<OnSelectDifficulty>,
+    mut scene: ResMut<GameScene>,
+) {
+    for _ in reader.iter() {
+        *scene = GameScene::InGame;
     }
 }
@@ -0,0 +1,909 @@
+use crate::events::*;
+use crate::hexgrid::PointyHexGrid;
+use crate::model::GameBoard;
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Frontier groups with more unknown tiles than this are not enumerated.
+pub const MAX_ENUMERATION_TILES: usize = 24;
+/// Layouts drawn from a frontier group too big to enumerate.
+pub const SAMPLES: usize = 1_000;
+
+pub struct SolverPlugin;
+
+impl Plugin for SolverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(on_try_hint_system);
+    }
+}
+
+/// Tiles whose content follows from the visible board.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deductions {
+    pub safe: Vec<PointyHexGrid>,
+    pub mines: Vec<PointyHexGrid>,
+}
+
+/// A move the visible board forces: open `target`, or flag it if `is_mine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hint {
+    pub target: PointyHexGrid,
+    pub is_mine: bool,
+}
+
+/// Finds every closed tile that is certainly safe or certainly a mine.
+/// Flags are trusted to be on mines.
+pub fn solve(game_board: &GameBoard) -> Deductions {
+    let mut frontier = Frontier::new(game_board);
+    frontier.deduce();
+    frontier.deductions()
+}
+
+/// The rules `solve` uses, from the simplest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Technique {
+    Single,
+    Subset,
+    Enumeration,
+}
+
+/// How a board was cleared without guessing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolveReport {
+    /// Times the visible board had to be read again after opening the deduced tiles.
+    pub rounds: usize,
+    /// Strongest rule any deduction needed.
+    pub hardest: Technique,
+}
+
+/// Plays on from the visible state by opening deduced safe tiles and flagging deduced mines,
+/// without using the total mine count. `None` if a guess is needed before every safe tile is open.
+pub fn clear_without_guessing(game_board: &GameBoard) -> Option<SolveReport> {
+    let mut game_board = game_board.clone();
+    let mut report = SolveReport {
+        rounds: 0,
+        hardest: Technique::Single,
+    };
+    while game_board.count_closed_safe_tiles() > 0 {
+        let mut frontier = Frontier::new(&game_board);
+        report.hardest = report.hardest.max(frontier.deduce()?);
+        report.rounds += 1;
+        let deductions = frontier.deductions();
+        for grid in deductions.safe {
+            game_board.open_region(grid);
+        }
+        for grid in deductions.mines {
+            game_board.cycle_marker(grid);
+        }
+    }
+    Some(report)
+}
+
+/// One forced move, preferring a safe tile to open over a mine to flag.
+pub fn hint(game_board: &GameBoard) -> Option<Hint> {
+    let deductions = solve(game_board);
+    let safe = deductions.safe.into_iter().map(|target| Hint {
+        target,
+        is_mine: false,
+    });
+    let mines = deductions.mines.into_iter().map(|target| Hint {
+        target,
+        is_mine: true,
+    });
+    safe.chain(mines).next()
+}
+
+/// Chance that each closed, unflagged tile is a mine.
+#[derive(Debug, Clone, Default)]
+pub struct MineProbabilities {
+    pub tiles: HashMap<PointyHexGrid, f32>,
+    /// False if a frontier group was too big to enumerate and its layouts were estimated
+    /// by sampling instead.
+    pub is_exact: bool,
+}
+
+/// Weighs every mine layout that fits the visible hints and the total `count_mines()`.
+/// Flags are trusted to be on mines. Empty until the mines are placed.
+pub fn mine_probabilities(game_board: &GameBoard) -> MineProbabilities {
+    let mut probabilities = MineProbabilities {
+        tiles: HashMap::new(),
+        is_exact: true,
+    };
+    if game_board.is_mines_pending() {
+        return probabilities;
+    }
+    let mut frontier = Frontier::new(game_board);
+    frontier.deduce();
+
+    for (grid, known) in frontier.tiles.iter().zip(&frontier.known) {
+        if let Some(is_mine) = known {
+            probabilities
+                .tiles
+                .insert(*grid, if *is_mine { 1.0 } else { 0.0 });
+        }
+    }
+
+    // 数字に接していないタイルはどれも同じ確率になる
+    let frontier_tiles = frontier.tiles.iter().copied().collect::<HashSet<_>>();
+    let mut free_tiles = game_board
+        .grids()
+        .filter(|grid| {
+            let tile_state = game_board.get(*grid).unwrap();
+            !tile_state.is_open() && !tile_state.is_flag() && !frontier_tiles.contains(grid)
+        })
+        .collect::<Vec<_>>();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut groups = vec![];
+    for group in frontier.groups() {
+        let enumeration = match frontier.enumerate(&group) {
+            Some(enumeration) => Some(enumeration),
+            None => {
+                probabilities.is_exact = false;
+                frontier.sample(&group, SAMPLES, &mut rng)
+            }
+        };
+        match enumeration {
+            Some(enumeration) if enumeration.solutions() > 0.0 => groups.push((group, enumeration)),
+            // 配置が見つからなければ数字を無視して他と同じに扱う
+            _ => free_tiles.extend(group.iter().map(|tile| frontier.tiles[*tile])),
+        }
+    }
+
+    let known_mines = game_board
+        .grids()
+        .filter(|grid| {
+            let tile_state = game_board.get(*grid).unwrap();
+            tile_state.is_flag() || tile_state.is_open() && tile_state.is_mine()
+        })
+        .count()
+        + frontier
+            .known
+            .iter()
+            .filter(|known| **known == Some(true))
+            .count();
+    let remaining_mines = game_board.count_mines().saturating_sub(known_mines);
+
+    let layouts = groups
+        .iter()
+        .map(|(_, enumeration)| enumeration.layouts())
+        .collect::<Vec<_>>();
+    let all_layouts = layouts
+        .iter()
+        .fold(vec![1.0], |all, layouts| convolve(&all, layouts));
+    let weights = free_weights(all_layouts.len(), remaining_mines, free_tiles.len());
+    let total = all_layouts
+        .iter()
+        .zip(&weights)
+        .map(|(layouts, weight)| layouts * weight)
+        .sum::<f64>();
+    if total <= 0.0 || !total.is_finite() {
+        return probabilities;
+    }
+
+    for (i, (group, enumeration)) in groups.iter().enumerate() {
+        let others = layouts
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(vec![1.0], |all, (_, layouts)| convolve(&all, layouts));
+        let mut chances = vec![0.0; group.len()];
+        for (mines, (_, counts)) in &enumeration.by_mines {
+            let weight = others
+                .iter()
+                .enumerate()
+                .map(|(other_mines, layouts)| {
+                    layouts
+                        * weights
+                            .get(mines + other_mines)
+                            .copied()
+                            .unwrap_or_default()
+                })
+                .sum::<f64>();
+            for (chance, count) in chances.iter_mut().zip(counts) {
+                *chance += count * weight;
+            }
+        }
+        for (tile, chance) in group.iter().zip(chances) {
+            probabilities
+                .tiles
+                .insert(frontier.tiles[*tile], (chance / total) as f32);
+        }
+    }
+
+    if !free_tiles.is_empty() {
+        let expected_mines = all_layouts
+            .iter()
+            .zip(&weights)
+            .enumerate()
+            .map(|(mines, (layouts, weight))| {
+                layouts * weight * remaining_mines.saturating_sub(mines) as f64
+            })
+            .sum::<f64>();
+        let chance = (expected_mines / total / free_tiles.len() as f64) as f32;
+        for grid in free_tiles {
+            probabilities.tiles.insert(grid, chance);
+        }
+    }
+    probabilities
+}
+
+/// Multiplies two layout counts indexed by mines used.
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0; a.len() + b.len() - 1];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            product[i + j] += a * b;
+        }
+    }
+    product
+}
+
+/// Ways to place the mines the frontier does not use on the free tiles, scaled so the
+/// largest is 1, indexed by mines the frontier uses.
+fn free_weights(len: usize, remaining_mines: usize, free_tiles: usize) -> Vec<f64> {
+    let ln_weights = (0..len)
+        .map(|mines| {
+            remaining_mines
+                .checked_sub(mines)
+                .filter(|rest| *rest <= free_tiles)
+                .map(|rest| ln_choose(free_tiles, rest))
+        })
+        .collect::<Vec<_>>();
+    let max = ln_weights
+        .iter()
+        .flatten()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max);
+    ln_weights
+        .into_iter()
+        .map(|ln_weight| ln_weight.map_or(0.0, |ln_weight| (ln_weight - max).exp()))
+        .collect()
+}
+
+fn ln_choose(n: usize, k: usize) -> f64 {
+    (0..k.min(n - k))
+        .map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln())
+        .sum()
+}
+
+/// `mines` of the unknown tiles `tiles` are mines. Tiles are indices into `Frontier::tiles`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Constraint {
+    pub tiles: Vec<usize>,
+    pub mines: usize,
+}
+
+/// The closed, unflagged tiles next to open hints and what the hints say about them.
+#[derive(Debug, Clone)]
+pub(crate) struct Frontier {
+    pub tiles: Vec<PointyHexGrid>,
+    /// `Some(true)` for a deduced mine, `Some(false)` for a deduced safe tile.
+    pub known: Vec<Option<bool>>,
+    pub constraints: Vec<Constraint>,
+}
+
+impl Frontier {
+    pub fn new(game_board: &GameBoard) -> Self {
+        let mut frontier = Self {
+            tiles: vec![],
+            known: vec![],
+            constraints: vec![],
+        };
+        // 地雷がまだ置かれていなければ何もわからない
+        if game_board.is_mines_pending() {
+            return frontier;
+        }
+
+        let mut indices = HashMap::new();
+        for grid in game_board.grids() {
+            let tile_state = game_board.get(grid).unwrap();
+            if !tile_state.is_open() || tile_state.is_mine() {
+                continue;
+            }
+            let mut tiles = vec![];
+            let mut known_mines = 0;
+            for neighbor in game_board.adjacent_grids(grid) {
+                let neighbor_state = game_board.get(neighbor).unwrap();
+                if neighbor_state.is_flag() || neighbor_state.is_open() && neighbor_state.is_mine()
+                {
+                    known_mines += 1;
+                } else if !neighbor_state.is_open() {
+                    let index = *indices.entry(neighbor).or_insert_with(|| {
+                        frontier.tiles.push(neighbor);
+                        frontier.tiles.len() - 1
+                    });
+                    tiles.push(index);
+                }
+            }
+            if tiles.is_empty() {
+                continue;
+            }
+            tiles.sort_unstable();
+            let hint = game_board.count_adjacent_mines(grid).unwrap_or_default();
+            frontier.constraints.push(Constraint {
+                tiles,
+                // 旗が多すぎる数字は満たせないので 0 として扱う
+                mines: hint.saturating_sub(known_mines),
+            });
+        }
+        frontier.known = vec![None; frontier.tiles.len()];
+        frontier.simplify();
+        frontier
+    }
+
+    /// Applies the rules until nothing changes, falling back to a stronger rule only when the
+    /// simpler ones are stuck. Returns the strongest rule used, or `None` if nothing was found.
+    pub fn deduce(&mut self) -> Option<Technique> {
+        let mut hardest = None;
+        loop {
+            let technique = if self.apply_single_rules() {
+                Technique::Single
+            } else if self.apply_subset_rules() {
+                Technique::Subset
+            } else if self.apply_enumeration() {
+                Technique::Enumeration
+            } else {
+                return hardest;
+            };
+            hardest = hardest.max(Some(technique));
+        }
+    }
+
+    fn mark(&mut self, tiles: &[usize], is_mine: bool) -> bool {
+        let mut changed = false;
+        for &tile in tiles {
+            if self.known[tile].is_none() {
+                self.known[tile] = Some(is_mine);
+                changed = true;
+            }
+        }
+        if changed {
+            self.simplify();
+        }
+        changed
+    }
+
+    /// Drops deduced tiles from the constraints, and constraints with nothing left.
+    fn simplify(&mut self) {
+        let known = &self.known;
+        for constraint in &mut self.constraints {
+            let mines = constraint
+                .tiles
+                .iter()
+                .filter(|tile| known[**tile] == Some(true))
+                .count();
+            constraint.mines = constraint.mines.saturating_sub(mines);
+            constraint.tiles.retain(|tile| known[*tile].is_none());
+        }
+        self.constraints
+            .retain(|constraint| !constraint.tiles.is_empty());
+        self.constraints.sort_by(|a, b| a.tiles.cmp(&b.tiles));
+        self.constraints.dedup_by(|a, b| a.tiles == b.tiles);
+    }
+
+    /// A hint with no mines left makes its tiles safe; one with as many mines as tiles makes them mines.
+    fn apply_single_rules(&mut self) -> bool {
+        for i in 0..self.constraints.len() {
+            let Some(constraint) = self.constraints.get(i).cloned() else {
+                break;
+            };
+            if constraint.mines == 0 {
+                return self.mark(&constraint.tiles, false);
+            }
+            if constraint.mines >= constraint.tiles.len() {
+                return self.mark(&constraint.tiles, true);
+            }
+        }
+        false
+    }
+
+    /// If one hint's tiles are a subset of another's, the difference holds the difference in mines.
+    fn apply_subset_rules(&mut self) -> bool {
+        for a in &self.constraints {
+            for b in &self.constraints {
+                if a.tiles.len() >= b.tiles.len()
+                    || !a
+                        .tiles
+                        .iter()
+                        .all(|tile| b.tiles.binary_search(tile).is_ok())
+                {
+                    continue;
+                }
+                let rest = b
+                    .tiles
+                    .iter()
+                    .copied()
+                    .filter(|tile| a.tiles.binary_search(tile).is_err())
+                    .collect::<Vec<_>>();
+                let mines = b.mines.saturating_sub(a.mines);
+                if mines == 0 {
+                    return self.mark(&rest, false);
+                }
+                if mines == rest.len() {
+                    return self.mark(&rest, true);
+                }
+            }
+        }
+        false
+    }
+
+    /// Tries every mine layout of each small frontier group and keeps what all of them agree on.
+    fn apply_enumeration(&mut self) -> bool {
+        let mut changed = false;
+        for group in self.groups() {
+            let Some(enumeration) = self.enumerate(&group) else {
+                continue;
+            };
+            let solutions = enumeration.solutions();
+            if solutions == 0.0 {
+                continue;
+            }
+            let mine_counts = enumeration.mine_counts();
+            let mut safe = vec![];
+            let mut mines = vec![];
+            for (&tile, &count) in group.iter().zip(&mine_counts) {
+                if count == 0.0 {
+                    safe.push(tile);
+                } else if count == solutions {
+                    mines.push(tile);
+                }
+            }
+            changed |= self.mark(&safe, false);
+            changed |= self.mark(&mines, true);
+        }
+        changed
+    }
+
+    /// Splits the unknown tiles into groups linked by shared hints.
+    pub fn groups(&self) -> Vec<Vec<usize>> {
+        let mut parent = (0..self.tiles.len()).collect::<Vec<_>>();
+        fn find(parent: &mut [usize], tile: usize) -> usize {
+            let mut root = tile;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            parent[tile] = root;
+            root
+        }
+        for constraint in &self.constraints {
+            for pair in constraint.tiles.windows(2) {
+                let (a, b) = (find(&mut parent, pair[0]), find(&mut parent, pair[1]));
+                parent[a] = b;
+            }
+        }
+        let mut groups = BTreeMap::<usize, Vec<usize>>::new();
+        for constraint in &self.constraints {
+            for &tile in &constraint.tiles {
+                let root = find(&mut parent, tile);
+                groups.entry(root).or_default().push(tile);
+            }
+        }
+        groups
+            .into_values()
+            .map(|mut tiles| {
+                tiles.sort_unstable();
+                tiles.dedup();
+                tiles
+            })
+            .collect()
+    }
+
+    /// Counts the mine layouts of `group` that satisfy its hints, or `None` if it is too big.
+    pub fn enumerate(&self, group: &[usize]) -> Option<Enumeration> {
+        if group.len() > MAX_ENUMERATION_TILES {
+            return None;
+        }
+        let mut search = self.search(group);
+        search.run(0, 0);
+        Some(search.enumeration)
+    }
+
+    /// Estimates what `enumerate` would count from `samples` random layouts of `group`, or
+    /// `None` if no layout is found. Each layout is weighted by the inverse of the chance it
+    /// was drawn with, so the counts are unbiased up to a common factor of `samples`.
+    pub fn sample(
+        &self,
+        group: &[usize],
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Option<Enumeration> {
+        let mut search = self.search(group);
+        for _ in 0..samples {
+            search.run_random(rng);
+        }
+        Some(search.enumeration).filter(|enumeration| enumeration.solutions() > 0.0)
+    }
+
+    fn search(&self, group: &[usize]) -> Search {
+        let local = group
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| (*tile, i))
+            .collect::<HashMap<_, _>>();
+        let constraints = self
+            .constraints
+            .iter()
+            .filter(|constraint| local.contains_key(&constraint.tiles[0]))
+            .map(|constraint| Constraint {
+                tiles: constraint.tiles.iter().map(|tile| local[tile]).collect(),
+                mines: constraint.mines,
+            })
+            .collect::<Vec<_>>();
+        Search {
+            constraints,
+            assignment: vec![false; group.len()],
+            enumeration: Enumeration::default(),
+        }
+    }
+
+    pub fn deductions(&self) -> Deductions {
+        let mut deductions = Deductions::default();
+        for (grid, known) in self.tiles.iter().zip(&self.known) {
+            match known {
+                Some(false) => deductions.safe.push(*grid),
+                Some(true) => deductions.mines.push(*grid),
+                None => {}
+            }
+        }
+        deductions
+    }
+}
+
+/// Layouts of a frontier group, keyed by how many mines they use.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Enumeration {
+    /// Mines used -> (layouts, per-tile count of layouts with a mine there).
+    pub by_mines: BTreeMap<usize, (f64, Vec<f64>)>,
+}
+
+impl Enumeration {
+    pub fn solutions(&self) -> f64 {
+        self.by_mines.values().map(|(solutions, _)| solutions).sum()
+    }
+
+    pub fn mine_counts(&self) -> Vec<f64> {
+        let mut total = vec![];
+        for (_, counts) in self.by_mines.values() {
+            total.resize(counts.len(), 0.0);
+            for (total, count) in total.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+        total
+    }
+
+    /// Layouts indexed by mines used.
+    pub fn layouts(&self) -> Vec<f64> {
+        let len = self.by_mines.keys().last().map_or(0, |mines| mines + 1);
+        let mut layouts = vec![0.0; len];
+        for (mines, (solutions, _)) in &self.by_mines {
+            layouts[*mines] = *solutions;
+        }
+        layouts
+    }
+}
+
+struct Search {
+    constraints: Vec<Constraint>,
+    assignment: Vec<bool>,
+    enumeration: Enumeration,
+}
+
+impl Search {
+    fn run(&mut self, tile: usize, mines: usize) {
+        if tile == self.assignment.len() {
+            self.record(mines, 1.0);
+            return;
+        }
+        for is_mine in [false, true] {
+            self.assignment[tile] = is_mine;
+            if self.is_consistent(tile) {
+                self.run(tile + 1, mines + is_mine as usize);
+            }
+        }
+    }
+
+    /// Sets every tile to a random value the hints still allow and records the layout
+    /// weighted by how many choices there were. Nothing is recorded on a dead end.
+    fn run_random(&mut self, rng: &mut impl Rng) {
+        let mut mines = 0;
+        let mut weight = 1.0;
+        for tile in 0..self.assignment.len() {
+            let values = [false, true]
+                .into_iter()
+                .filter(|is_mine| {
+                    self.assignment[tile] = *is_mine;
+                    self.is_consistent(tile)
+                })
+                .collect::<Vec<_>>();
+            if values.is_empty() {
+                return;
+            }
+            let is_mine = values[rng.gen_range(0..values.len())];
+            self.assignment[tile] = is_mine;
+            mines += is_mine as usize;
+            weight *= values.len() as f64;
+        }
+        self.record(mines, weight);
+    }
+
+    fn record(&mut self, mines: usize, weight: f64) {
+        let (solutions, counts) = self
+            .enumeration
+            .by_mines
+            .entry(mines)
+            .or_insert_with(|| (0.0, vec![0.0; self.assignment.len()]));
+        *solutions += weight;
+        for (count, is_mine) in counts.iter_mut().zip(&self.assignment) {
+            if *is_mine {
+                *count += weight;
+            }
+        }
+    }
+
+    /// Whether every hint touching `tile` can still be met once tiles up to `tile` are set.
+    fn is_consistent(&self, tile: usize) -> bool {
+        self.constraints
+            .iter()
+            .filter(|constraint| constraint.tiles.contains(&tile))
+            .all(|constraint| {
+                let mines = constraint
+                    .tiles
+                    .iter()
+                    .filter(|t| **t <= tile && self.assignment[**t])
+                    .count();
+                let unset = constraint.tiles.iter().filter(|t| **t > tile).count();
+                mines <= constraint.mines && constraint.mines <= mines + unset
+            })
+    }
+}
+
+fn on_try_hint_system(
+    game_board: Res<GameBoard>,
+    mut reader: EventReader<OnTryHint>,
+    mut writer: EventWriter<OnHint>,
+) {
+    if reader.iter().last().is_none() {
+        return;
+    }
+    match hint(&game_board) {
+        Some(hint) => writer.send(OnHint {
+            target: hint.target,
+            is_mine: hint.is_mine,
+        }),
+        None => info!("no forced move on this board"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BoardShape, FirstClickSafety, GameRule, MineCount};
+    use proptest::prelude::*;
+
+    fn grid(x: i32, y: i32) -> PointyHexGrid {
+        PointyHexGrid { x, y }
+    }
+
+    fn board(text: &str) -> GameBoard {
+        GameBoard::from_board_text(text).unwrap()
+    }
+
+    // タイルの位置は使わないので適当な座標を割り当てる
+    fn new_frontier(tiles: usize, constraints: &[(Vec<usize>, usize)]) -> Frontier {
+        Frontier {
+            tiles: (0..tiles as i32).map(|x| grid(x, 0)).collect(),
+            known: vec![None; tiles],
+            constraints: constraints
+                .iter()
+                .map(|(tiles, mines)| Constraint {
+                    tiles: tiles.clone(),
+                    mines: *mines,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_single_tile_rules() {
+        // (1, 1) の周りで閉じているのは (2, 0) だけで，数字は 1
+        let game_board = board("2\n#OM\nOOO\nOO#");
+        assert_eq!(
+            solve(&game_board),
+            Deductions {
+                safe: vec![],
+                mines: vec![grid(2, 0)],
+            }
+        );
+        assert_eq!(
+            hint(&game_board),
+            Some(Hint {
+                target: grid(2, 0),
+                is_mine: true
+            })
+        );
+
+        // 旗で数字が満たされていれば残りは安全
+        let mut game_board = board("2\n#.M\nOO.\nOO#");
+        game_board.cycle_marker(grid(2, 0));
+        let deductions = solve(&game_board);
+        assert_eq!(deductions.safe.len(), 2);
+        assert!(deductions.safe.contains(&grid(1, 0)));
+        assert!(deductions.safe.contains(&grid(2, 1)));
+        assert!(deductions.mines.is_empty());
+    }
+
+    #[test]
+    fn test_subset_rule() {
+        // {0, 1} に 1 つなら {0, 1, 2} の残り 2 は安全，{0, 1, 2, 3} の残り 2, 3 は地雷
+        let mut frontier = new_frontier(4, &[(vec![0, 1], 1), (vec![0, 1, 2], 1)]);
+        assert!(!frontier.apply_single_rules());
+        assert!(frontier.apply_subset_rules());
+        assert_eq!(frontier.known, vec![None, None, Some(false), None]);
+
+        let mut frontier = new_frontier(4, &[(vec![0, 1], 1), (vec![0, 1, 2, 3], 3)]);
+        assert!(frontier.apply_subset_rules());
+        assert_eq!(frontier.known, vec![None, None, Some(true), Some(true)]);
+    }
+
+    #[test]
+    fn test_enumeration_finds_forced_tiles() {
+        // 3 が地雷でなければ 0 と 1 が両方地雷になって最初の数字に合わない
+        let mut frontier = new_frontier(4, &[(vec![0, 1, 2], 1), (vec![0, 3], 1), (vec![1, 3], 1)]);
+        assert!(!frontier.apply_single_rules());
+        assert!(!frontier.apply_subset_rules());
+        let enumeration = frontier.enumerate(&[0, 1, 2, 3]).unwrap();
+        assert_eq!(enumeration.solutions(), 1.0);
+        assert!(frontier.apply_enumeration());
+        assert_eq!(
+            frontier.known,
+            vec![Some(false), Some(false), Some(true), Some(true)]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_frontier() {
+        let mut frontier = new_frontier(3, &[(vec![0, 1], 1), (vec![1, 2], 1)]);
+        assert!(!frontier.apply_single_rules());
+        assert!(!frontier.apply_subset_rules());
+        assert!(!frontier.apply_enumeration());
+        assert_eq!(frontier.groups(), vec![vec![0, 1, 2]]);
+        let enumeration = frontier.enumerate(&[0, 1, 2]).unwrap();
+        assert_eq!(enumeration.solutions(), 2.0);
+        assert_eq!(enumeration.mine_counts(), vec![1.0, 1.0, 1.0]);
+        assert_eq!(frontier.deductions(), Deductions::default());
+    }
+
+    #[test]
+    fn test_clear_without_guessing() {
+        // (0, 1) と (1, 2) が 0 なので隣は安全で，残る (2, 0) が (1, 1) の地雷
+        assert_eq!(
+            clear_without_guessing(&board("2\n#.M\nOO.\nOO#")),
+            Some(SolveReport {
+                rounds: 1,
+                hardest: Technique::Single,
+            })
+        );
+        // 何も開いていなければ当てずっぽうしかない
+        assert_eq!(clear_without_guessing(&board("2\n#M.\n..M\n..#")), None);
+    }
+
+    #[test]
+    fn test_deduce_reports_the_hardest_technique() {
+        let mut frontier = new_frontier(4, &[(vec![0, 1, 2], 1), (vec![0, 3], 1), (vec![1, 3], 1)]);
+        assert_eq!(frontier.deduce(), Some(Technique::Enumeration));
+        let mut frontier = new_frontier(3, &[(vec![0, 1], 1), (vec![0, 1, 2], 1)]);
+        assert_eq!(frontier.deduce(), Some(Technique::Subset));
+        let mut frontier = new_frontier(3, &[(vec![0, 1], 1), (vec![1, 2], 1)]);
+        assert_eq!(frontier.deduce(), None);
+    }
+
+    #[test]
+    fn test_sample() {
+        let frontier = new_frontier(3, &[(vec![0, 1], 1), (vec![1, 2], 1)]);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let enumeration = frontier.sample(&[0, 1, 2], 20, &mut rng).unwrap();
+        // 配置は 2 通りなので，重みの平均は 2 になる
+        assert_eq!(enumeration.solutions(), 40.0);
+        // 中央だけが地雷か，両端が地雷か
+        assert!(enumeration
+            .by_mines
+            .keys()
+            .all(|mines| [1, 2].contains(mines)));
+        let mine_counts = enumeration.mine_counts();
+        assert_eq!(mine_counts[0], mine_counts[2]);
+        assert_eq!(mine_counts[0] + mine_counts[1], 40.0);
+    }
+
+    #[test]
+    fn test_sample_is_unbiased() {
+        // 最初のタイルから決めていくと 0 番が地雷になる配置を半分の確率で引くが，重みで打ち消す
+        let frontier = new_frontier(3, &[(vec![0, 1, 2], 1)]);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let enumeration = frontier.sample(&[0, 1, 2], 3000, &mut rng).unwrap();
+        assert!((enumeration.solutions() / 3000.0 - 3.0).abs() < 0.2);
+        for count in enumeration.mine_counts() {
+            assert!((count / enumeration.solutions() - 1.0 / 3.0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_mine_probabilities() {
+        // 開いたタイルが無ければどこも 2 / 7
+        let probabilities = mine_probabilities(&board("2\n#M.\n..M\n..#"));
+        assert!(probabilities.is_exact);
+        assert_eq!(probabilities.tiles.len(), 7);
+        for chance in probabilities.tiles.values() {
+            assert!((chance - 2.0 / 7.0).abs() < 1e-6);
+        }
+
+        let probabilities = mine_probabilities(&board("2\n#OM\nOOO\nOO#"));
+        assert_eq!(probabilities.tiles.len(), 1);
+        assert_eq!(probabilities.tiles[&grid(2, 0)], 1.0);
+    }
+
+    #[test]
+    fn test_no_hint_before_mines_are_placed() {
+        let game_board = GameBoard::generate(
+            BoardShape::hexagon(3),
+            MineCount::Exact(3),
+            "s",
+            FirstClickSafety::Tile,
+        );
+        assert_eq!(hint(&game_board), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_deductions_are_correct(
+            seed in "[a-z]{1,8}",
+            density in 0.05_f32..0.35,
+            opens in prop::collection::vec((0..9, 0..9), 1..6),
+        ) {
+            let mut game_board =
+                GameBoard::generate(BoardShape::hexagon(5), MineCount::Density(density), &seed, FirstClickSafety::Opening);
+            let rule = GameRule { lives: usize::MAX, ..Default::default() };
+            for (x, y) in opens {
+                game_board.try_open(grid(x, y), &rule);
+            }
+            let deductions = solve(&game_board);
+            for grid in deductions.safe {
+                prop_assert!(!game_board.get(grid).unwrap().is_mine());
+            }
+            for grid in deductions.mines {
+                prop_assert!(game_board.get(grid).unwrap().is_mine());
+            }
+        }
+
+        #[test]
+        fn prop_mine_probabilities_add_up_to_remaining_mines(
+            seed in "[a-z]{1,8}",
+            density in 0.05_f32..0.35,
+            opens in prop::collection::vec((0..9, 0..9), 1..6),
+        ) {
+            let mut game_board =
+                GameBoard::generate(BoardShape::hexagon(5), MineCount::Density(density), &seed, FirstClickSafety::Opening);
+            let rule = GameRule { lives: usize::MAX, ..Default::default() };
+            for (x, y) in opens {
+                game_board.try_open(grid(x, y), &rule);
+            }
+            let probabilities = mine_probabilities(&game_board);
+            let open_mines = game_board
+                .grids()
+                .filter(|grid| game_board.get(*grid).is_some_and(|t| t.is_open() && t.is_mine()))
+                .count();
+            let expected = probabilities.tiles.values().sum::<f32>();
+            prop_assert!((expected - (game_board.count_mines() - open_mines) as f32).abs() < 1e-3);
+            for (grid, chance) in probabilities.tiles {
+                prop_assert!((0.0..=1.0 + 1e-6).contains(&chance));
+                if chance == 0.0 {
+                    prop_assert!(!game_board.get(grid).unwrap().is_mine());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::events::*;
+use crate::model::GameBoard;
+use crate::scene::GameScene;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameStats>()
+            .add_system(tick_game_timer_system)
+            .add_system(count_move_tile_system)
+            .add_system(count_undo_tile_system)
+            .add_system(on_reset_board_system);
+    }
+}
+
+/// Clock and click counters of the current game.
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct GameStats {
+    /// Seconds played. The clock starts on the first move.
+    pub elapsed: f32,
+    pub is_started: bool,
+    pub opens: usize,
+    pub flags: usize,
+    pub undos: usize,
+    /// Moves played again by redo. They are not clicks.
+    #[serde(default)]
+    pub redos: usize,
+    /// 3BV of the board, taken on the first move once the mines are placed.
+    pub board_3bv: usize,
+}
+
+impl GameStats {
+    pub fn clicks(&self) -> usize {
+        self.opens + self.flags
+    }
+
+    /// Ratio of the board's 3BV to the clicks used. Chording can push it above 1.0.
+    pub fn efficiency(&self) -> f32 {
+        if self.clicks() == 0 {
+            0.0
+        } else {
+            self.board_3bv as f32 / self.clicks() as f32
+        }
+    }
+}
+
+fn tick_game_timer_system(
+    time: Res<Time>,
+    game_scene: Res<GameScene>,
+    mut game_stats: ResMut<GameStats>,
+) {
+    if let GameScene::InGame = *game_scene {
+        if game_stats.is_started {
+            game_stats.elapsed += time.delta_seconds();
+        }
+    }
+}
+
+fn count_move_tile_system(
+    mut reader: EventReader<OnMoveTile>,
+    mut redo_reader: EventReader<OnRedoTile>,
+    game_scene: Res<GameScene>,
+    mut game_stats: ResMut<GameStats>,
+    game_board: Res<GameBoard>,
+) {
+    // リプレイの操作は自分の記録に数えない
+    if let GameScene::Replay = *game_scene {
+        reader.clear();
+        redo_reader.clear();
+        return;
+    }
+    // redo の手は同じフレームの OnMoveTile と対になっている
+    let mut redone = redo_reader
+        .iter()
+        .map(|event| event.move_tile.clone())
+        .collect::<Vec<_>>();
+    for event in reader.iter() {
+        if let Some(index) = redone.iter().position(|move_tile| move_tile == event) {
+            redone.swap_remove(index);
+            game_stats.redos += 1;
+            continue;
+        }
+        if !game_stats.is_started {
+            game_stats.is_started = true;
+            game_stats.board_3bv = game_board.count_3bv();
+        }
+        match event {
+            OnMoveTile::Open { .. } | OnMoveTile::OpenRegion { .. } => game_stats.opens += 1,
+            OnMoveTile::Flag { .. }
+            | OnMoveTile::Question { .. }
+            | OnMoveTile::ClearMark { .. } => game_stats.flags += 1,
+        }
+    }
+}
+
+fn count_undo_tile_system(
+    mut reader: EventReader<OnUndoTile>,
+    game_scene: Res<GameScene>,
+    mut game_stats: ResMut<GameStats>,
+) {
+    if let GameScene::Replay = *game_scene {
+        reader.clear();
+        return;
+    }
+    for _ in reader.iter() {
+        game_stats.undos += 1;
+    }
+}
+
+fn on_reset_board_system(mut reader: EventReader<OnResetBoard>, mut game_stats: ResMut<GameStats>) {
+    if reader.iter().last().is_some() {
+        *game_stats = GameStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexgrid::PointyHexGrid;
+
+    #[test]
+    fn test_redo_is_not_a_click() {
+        let mut app = App::new();
+        app.add_event::<OnMoveTile>()
+            .add_event::<OnRedoTile>()
+            .insert_resource(GameScene::InGame)
+            .insert_resource(GameBoard::new(2))
+            .init_resource::<GameStats>()
+            .add_system(count_move_tile_system);
+
+        let open = OnMoveTile::Open {
+            target: PointyHexGrid { x: 1, y: 1 },
+        };
+        let flag = OnMoveTile::Flag {
+            target: PointyHexGrid { x: 0, y: 1 },
+        };
+        app.world.send_event(open.clone());
+        app.world.send_event(flag.clone());
+        app.update();
+        assert_eq!(app.world.resource::<GameStats>().clicks(), 2);
+
+        // redo した手だけは数えない
+        app.world.send_event(flag.clone());
+        app.world.send_event(OnRedoTile {
+            move_tile: open.clone(),
+        });
+        app.world.send_event(open);
+        app.update();
+        let game_stats = app.world.resource::<GameStats>();
+        assert_eq!(game_stats.opens, 1);
+        assert_eq!(game_stats.flags, 2);
+        assert_eq!(game_stats.redos, 1);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::hexgrid::PointyHexGrid;
+use crate::model::{Game, GameBoard};
+use crate::solver;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::time::{Duration, Instant};
+
+/// A move a `Strategy` wants to make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub target: PointyHexGrid,
+    /// Flag `target` instead of opening it.
+    pub is_flag: bool,
+    /// The strategy could not tell whether `target` is safe.
+    pub is_guess: bool,
+}
+
+/// Decides how a bot plays a `Game`.
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    /// Moves to make in order on the current board. Empty if the strategy gives up.
+    fn next_moves(&mut self, game_board: &GameBoard) -> Vec<Move>;
+}
+
+/// Opens a random closed tile every move.
+pub struct RandomStrategy {
+    rng: ChaCha8Rng,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn next_moves(&mut self, game_board: &GameBoard) -> Vec<Move> {
+        random_open(game_board, &mut self.rng).into_iter().collect()
+    }
+}
+
+/// Plays every move the solver finds, and opens a random closed tile when it finds none.
+pub struct SolverStrategy {
+    rng: ChaCha8Rng,
+}
+
+impl SolverStrategy {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for SolverStrategy {
+    fn name(&self) -> &'static str {
+        "solver"
+    }
+
+    fn next_moves(&mut self, game_board: &GameBoard) -> Vec<Move> {
+        let moves = forced_moves(game_board);
+        if !moves.is_empty() {
+            return moves;
+        }
+        random_open(game_board, &mut self.rng).into_iter().collect()
+    }
+}
+
+/// Plays every move the solver finds, and opens the tile least likely to be a mine when it finds none.
+pub struct ProbabilityStrategy {
+    rng: ChaCha8Rng,
+}
+
+impl ProbabilityStrategy {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for ProbabilityStrategy {
+    fn name(&self) -> &'static str {
+        "probability"
+    }
+
+    fn next_moves(&mut self, game_board: &GameBoard) -> Vec<Move> {
+        let moves = forced_moves(game_board);
+        if !moves.is_empty() {
+            return moves;
+        }
+        let probabilities = solver::mine_probabilities(game_board);
+        // 確率が同じなら盤面の順で選んで結果を再現できるようにする
+        let safest = probabilities
+            .tiles
+            .into_iter()
+            .min_by(|(a, a_chance), (b, b_chance)| {
+                a_chance
+                    .total_cmp(b_chance)
+                    .then((a.y, a.x).cmp(&(b.y, b.x)))
+            })
+            .map(|(target, _)| Move {
+                target,
+                is_flag: false,
+                is_guess: true,
+            });
+        match safest {
+            Some(safest) => vec![safest],
+            // 地雷を置く前は確率が出ないので適当に開ける
+            None => random_open(game_board, &mut self.rng).into_iter().collect(),
+        }
+    }
+}
+
+fn forced_moves(game_board: &GameBoard) -> Vec<Move> {
+    let deductions = solver::solve(game_board);
+    let safe = deductions.safe.into_iter().map(|target| Move {
+        target,
+        is_flag: false,
+        is_guess: false,
+    });
+    let mines = deductions.mines.into_iter().map(|target| Move {
+        target,
+        is_flag: true,
+        is_guess: false,
+    });
+    safe.chain(mines).collect()
+}
+
+fn random_open(game_board: &GameBoard, rng: &mut ChaCha8Rng) -> Option<Move> {
+    let target = game_board
+        .grids()
+        .filter(|grid| {
+            game_board
+                .get(*grid)
+                .is_some_and(|t| !t.is_open() && !t.is_flag())
+        })
+        .choose(rng)?;
+    Some(Move {
+        target,
+        is_flag: false,
+        // 地雷を置く前の一手目は外れない
+        is_guess: !game_board.is_mines_pending(),
+    })
+}
+
+/// How one game played by a `Strategy` went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayReport {
+    pub is_won: bool,
+    pub guesses: usize,
+    pub moves: usize,
+    pub duration: Duration,
+}
+
+/// Lets `strategy` play `game` until it is won, lost or the strategy has nothing left to do.
+pub fn play(game: &mut Game, strategy: &mut dyn Strategy) -> PlayReport {
+    let started = Instant::now();
+    let mut guesses = 0;
+    let mut moves = 0;
+    while !game.is_over() && !game.is_cleared() {
+        let mut is_stuck = true;
+        for next_move in strategy.next_moves(game.board()) {
+            let outcome = if next_move.is_flag {
+                game.flag(next_move.target)
+            } else {
+                game.open(next_move.target)
+            };
+            if outcome.is_ignored() {
+                continue;
+            }
+            is_stuck = false;
+            moves += 1;
+            guesses += next_move.is_guess as usize;
+            if game.is_over() || game.is_cleared() {
+                break;
+            }
+        }
+        if is_stuck {
+            break;
+        }
+    }
+    PlayReport {
+        is_won: game.is_cleared(),
+        guesses,
+        moves,
+        duration: started.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BoardShape, BoardSource, FirstClickSafety, GameRule, MineCount, WinRule};
+
+    fn rule() -> GameRule {
+        GameRule {
+            win_rule: WinRule::SafeTilesOpened,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_solver_clears_without_guessing() {
+        let game_board = GameBoard::from_board_text("2\n#.M\nOO.\nOO#").unwrap();
+        for strategy in [
+            &mut SolverStrategy::new(0) as &mut dyn Strategy,
+            &mut ProbabilityStrategy::new(0),
+        ] {
+            let mut game = Game::new(game_board.clone(), rule());
+            let report = play(&mut game, strategy);
+            assert!(report.is_won);
+            assert_eq!(report.guesses, 0);
+        }
+    }
+
+    #[test]
+    fn test_every_strategy_finishes_the_game() {
+        let source = BoardSource::Generate {
+            shape: BoardShape::hexagon(4),
+            mine_count: MineCount::Density(0.2),
+            seed: Some("strategy".to_string()),
+            first_click: FirstClickSafety::Opening,
+        };
+        for strategy in [
+            &mut RandomStrategy::new(0) as &mut dyn Strategy,
+            &mut SolverStrategy::new(0),
+            &mut ProbabilityStrategy::new(0),
+        ] {
+            let mut game = Game::from_source(&source, rule()).unwrap();
+            let report = play(&mut game, strategy);
+            assert!(game.is_over() || game.is_cleared());
+            assert_eq!(report.is_won, game.is_cleared());
+            assert!(report.guesses <= report.moves);
+        }
+    }
+}
@@ -1,6 +1,12 @@
 use crate::events::*;
 use crate::hexgrid;
 use crate::hexgrid::PointyHexGrid;
+use crate::level::Levels;
+use crate::preset::{CustomField, DifficultyMenu, PRESETS};
+use crate::replay::ReplayPlayer;
+use crate::scene::GameScene;
+use crate::solver::{self, Hint};
+use crate::stats::GameStats;
 use crate::{model, Config, CursorWorldPosition};
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use std::collections::HashMap;
@@ -10,24 +16,50 @@ pub struct ViewPlugin;
 impl Plugin for ViewPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(SelectedTile::default())
+            .insert_resource(HintedTile::default())
+            .insert_resource(Heatmap::default())
             .add_startup_system(setup_view)
             .add_startup_system(setup_game_over)
             .add_startup_system(setup_game_clear)
             .add_startup_system(setup_sound)
             .add_startup_system(setup_mines_hint_text)
+            .add_startup_system(setup_stats_text)
+            .add_startup_system(setup_level_select)
+            .add_startup_system(setup_difficulty_menu)
+            .add_startup_system(setup_replay_view)
             .add_system(recolor_tile_selected_system)
+            .add_system(on_hint_system)
+            .add_system(clear_hint_system)
+            .add_system(update_heatmap_system)
             .add_system(on_move_tile_system)
             .add_system(on_game_over_system)
             .add_system(on_game_clear_system)
             .add_system(on_retry_system)
+            .add_system(on_rescue_system)
+            .add_system(reveal_wrong_flags_system)
+            .add_system(on_reset_board_system)
             .add_system(on_undo_tile_system)
-            .add_system(on_flag_tile_system);
+            .add_system(on_flag_tile_system)
+            .add_system(update_stats_text_system)
+            .add_system(update_level_select_system)
+            .add_system(update_difficulty_menu_system)
+            .add_system(update_ghost_cursor_system)
+            .add_system(update_replay_text_system);
     }
 }
 
 #[derive(Component, Default)]
 pub struct Tilemap;
 
+/// Where `world_position` is on the tilemap, in units of the tile radius.
+pub fn tilemap_point(tilemap_transform: &Transform, world_position: Vec2, config: &Config) -> Vec2 {
+    let local = tilemap_transform
+        .compute_matrix()
+        .inverse()
+        .transform_point3(Vec3::from((world_position, 0.0)));
+    local.truncate() / config.tile_size
+}
+
 #[derive(Component, Default)]
 struct TileHexGrid {
     pub grid: PointyHexGrid,
@@ -47,6 +79,45 @@ struct SelectedTile {
     pub grid: PointyHexGrid,
 }
 
+/// The forced move shown after pressing the hint key, until the board changes.
+#[derive(Resource, Default)]
+struct HintedTile {
+    pub hint: Option<Hint>,
+}
+
+/// Mine probabilities tinting the closed tiles while shown.
+#[derive(Resource, Default)]
+struct Heatmap {
+    pub is_visible: bool,
+    pub probabilities: HashMap<PointyHexGrid, f32>,
+}
+
+/// Colour of an unselected tile: the hint wins over the heatmap.
+fn tile_color(
+    grid: PointyHexGrid,
+    hinted_tile: &HintedTile,
+    heatmap: &Heatmap,
+    config: &Config,
+) -> Color {
+    match hinted_tile.hint {
+        Some(hint) if hint.target == grid && hint.is_mine => return config.tile_hint_mine_color,
+        Some(hint) if hint.target == grid => return config.tile_hint_safe_color,
+        _ => {}
+    }
+    match heatmap.probabilities.get(&grid) {
+        Some(chance) if heatmap.is_visible => {
+            let [r0, g0, b0, _] = config.tile_color.as_rgba_f32();
+            let [r1, g1, b1, _] = config.tile_heatmap_color.as_rgba_f32();
+            Color::rgb(
+                r0 + (r1 - r0) * chance,
+                g0 + (g1 - g0) * chance,
+                b0 + (b1 - b0) * chance,
+            )
+        }
+        _ => config.tile_color,
+    }
+}
+
 #[derive(Bundle, Default)]
 struct TilemapBundle {
     tilemap: Tilemap,
@@ -60,16 +131,41 @@ fn setup_view(
     mut commands: Commands,
     config: Res<Config>,
     game_board: Res<model::GameBoard>,
+    windows: Res<Windows>,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let tiles_per_side = game_board.tiles_per_side();
-    let board_center_grid = hexgrid::pointy_hex_grid_to_cartesian(PointyHexGrid {
-        x: (tiles_per_side - 1) as i32,
-        y: (tiles_per_side - 1) as i32,
+    spawn_tilemap(
+        &mut commands,
+        &config,
+        &game_board,
+        &windows,
+        &asset_server,
+        &mut meshes,
+        &mut materials,
+    );
+}
+
+fn spawn_tilemap(
+    commands: &mut Commands,
+    config: &Config,
+    game_board: &model::GameBoard,
+    windows: &Windows,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    // 大きな盤面でも画面に収まるように盤面を縮める．HUD はそのままの大きさで残す
+    let (min, max) = game_board.shape().bounds();
+    let scale = windows.get_primary().map_or(1.0, |window| {
+        // タイル 1 枚分の余白を足す
+        let board_size = (max - min + Vec2::splat(2.0)) * config.tile_size;
+        let screen_size = Vec2::new(window.width(), window.height()) * config.tilemap_screen_ratio;
+        1.0 / (board_size / screen_size).max_element().max(1.0)
     });
-    let tilemap_translation = -Vec3::from((board_center_grid, 0.0)) * config.tile_size;
+    let board_center = (min + max) / 2.0;
+    let tilemap_translation = -Vec3::from((board_center, 0.0)) * config.tile_size * scale;
 
     let tile_mesh = shape::RegularPolygon::new(config.tile_size * config.tile_gap_scale, 6);
     let tile_edge_mesh =
@@ -80,7 +176,7 @@ fn setup_view(
     let tile_text_font = asset_server.load(&config.tile_text_font_path);
 
     let mut tilemap_entity_commands = commands.spawn(TilemapBundle {
-        transform: Transform::from_translation(tilemap_translation),
+        transform: Transform::from_translation(tilemap_translation).with_scale(Vec3::splat(scale)),
         ..Default::default()
     });
 
@@ -88,67 +184,58 @@ fn setup_view(
     let mut text_ids = HashMap::<PointyHexGrid, Entity>::new();
 
     tilemap_entity_commands.add_children(|parent| {
-        for x in 0..(2 * tiles_per_side - 1) {
-            for y in 0..(2 * tiles_per_side - 1) {
-                let grid = PointyHexGrid {
-                    x: x as i32,
-                    y: y as i32,
-                };
-                if let Some(_) = game_board.get(grid) {
-                    let tile_position =
-                        hexgrid::pointy_hex_grid_to_cartesian(grid) * config.tile_size;
-                    parent.spawn((
-                        TileEdge,
-                        TileHexGrid { grid },
-                        MaterialMesh2dBundle {
-                            transform: Transform::from_translation(Vec3::from((
-                                tile_position,
-                                config.tile_edge_layer,
-                            ))),
-                            mesh: meshes.add(tile_edge_mesh.into()).into(),
-                            material: materials.add(tile_edge_color_material.clone()),
-                            ..Default::default()
-                        },
-                    ));
-                    let material_mesh_id = parent
-                        .spawn((
-                            TileHexGrid { grid },
-                            MaterialMesh2dBundle {
-                                transform: Transform::from_translation(Vec3::from((
-                                    tile_position,
-                                    config.tile_layer,
-                                ))),
-                                mesh: meshes.add(tile_mesh.into()).into(),
-                                material: materials.add(tile_color_material.clone()),
-                                ..Default::default()
-                            },
-                        ))
-                        .id();
-
-                    let (value, color) = get_tile_text_and_color(&game_board, grid, &config);
-                    let tile_text_style = TextStyle {
-                        font: tile_text_font.clone(),
-                        font_size: config.tile_text_size,
-                        color,
-                    };
-                    let text_id = parent
-                        .spawn((
-                            TileHexGrid { grid },
-                            Text2dBundle {
-                                transform: Transform::from_translation(Vec3::from((
-                                    tile_position,
-                                    config.tile_text_layer,
-                                ))),
-                                text: Text::from_section(value, tile_text_style)
-                                    .with_alignment(TextAlignment::CENTER),
-                                ..Default::default()
-                            },
-                        ))
-                        .id();
-                    material_mesh_ids.insert(grid, material_mesh_id);
-                    text_ids.insert(grid, text_id);
-                }
-            }
+        for grid in game_board.grids() {
+            let tile_position = hexgrid::pointy_hex_grid_to_cartesian(grid) * config.tile_size;
+            parent.spawn((
+                TileEdge,
+                TileHexGrid { grid },
+                MaterialMesh2dBundle {
+                    transform: Transform::from_translation(Vec3::from((
+                        tile_position,
+                        config.tile_edge_layer,
+                    ))),
+                    mesh: meshes.add(tile_edge_mesh.into()).into(),
+                    material: materials.add(tile_edge_color_material.clone()),
+                    ..Default::default()
+                },
+            ));
+            let material_mesh_id = parent
+                .spawn((
+                    TileHexGrid { grid },
+                    MaterialMesh2dBundle {
+                        transform: Transform::from_translation(Vec3::from((
+                            tile_position,
+                            config.tile_layer,
+                        ))),
+                        mesh: meshes.add(tile_mesh.into()).into(),
+                        material: materials.add(tile_color_material.clone()),
+                        ..Default::default()
+                    },
+                ))
+                .id();
+
+            let (value, color) = get_tile_text_and_color(game_board, grid, config, false);
+            let tile_text_style = TextStyle {
+                font: tile_text_font.clone(),
+                font_size: config.tile_text_size,
+                color,
+            };
+            let text_id = parent
+                .spawn((
+                    TileHexGrid { grid },
+                    Text2dBundle {
+                        transform: Transform::from_translation(Vec3::from((
+                            tile_position,
+                            config.tile_text_layer,
+                        ))),
+                        text: Text::from_section(value, tile_text_style)
+                            .with_alignment(TextAlignment::CENTER),
+                        ..Default::default()
+                    },
+                ))
+                .id();
+            material_mesh_ids.insert(grid, material_mesh_id);
+            text_ids.insert(grid, text_id);
         }
     });
 
@@ -158,6 +245,7 @@ fn setup_view(
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn recolor_tile_selected_system(
     tile_color_query: Query<(&TileHexGrid, &mut Handle<ColorMaterial>), Without<TileEdge>>,
     tilemap_query: Query<&Transform, With<Tilemap>>,
@@ -165,19 +253,32 @@ fn recolor_tile_selected_system(
     mut materials: ResMut<Assets<ColorMaterial>>,
     config: Res<Config>,
     tile_ids: Res<TileIds>,
+    hinted_tile: Res<HintedTile>,
+    heatmap: Res<Heatmap>,
     mut selexted_tile: ResMut<SelectedTile>,
 ) {
-    let tilemap_transform = tilemap_query.single();
-    let cursor_grid = hexgrid::cartesian_point_to_nearest_pointy_hex_grid(
-        (cursor_world_position.position - tilemap_transform.translation.truncate())
-            / config.tile_size,
-    );
+    let cursor_grid = hexgrid::cartesian_point_to_nearest_pointy_hex_grid(tilemap_point(
+        tilemap_query.single(),
+        cursor_world_position.position,
+        &config,
+    ));
+
+    // ヒントやヒートマップが変わるか，タイルが作り直されたら全部塗り直す
+    if hinted_tile.is_changed() || heatmap.is_changed() || tile_ids.is_changed() {
+        for (tile_hex_grid, color_handle) in tile_color_query.iter() {
+            if let Some(color_material) = materials.get_mut(color_handle) {
+                color_material.color =
+                    tile_color(tile_hex_grid.grid, &hinted_tile, &heatmap, &config);
+            }
+        }
+    }
 
     // 色を戻す
     if let Some(grid_entity) = tile_ids.material_mesh_ids.get(&selexted_tile.grid) {
         if let Ok((_, color_handle)) = tile_color_query.get(*grid_entity) {
-            if let Some(color_material) = materials.get_mut(&color_handle) {
-                color_material.color = config.tile_color;
+            if let Some(color_material) = materials.get_mut(color_handle) {
+                color_material.color =
+                    tile_color(selexted_tile.grid, &hinted_tile, &heatmap, &config);
             }
         }
     }
@@ -187,13 +288,62 @@ fn recolor_tile_selected_system(
     //色を付ける
     if let Some(grid_entity) = tile_ids.material_mesh_ids.get(&selexted_tile.grid) {
         if let Ok((_, color_handle)) = tile_color_query.get(*grid_entity) {
-            if let Some(color_material) = materials.get_mut(&color_handle) {
+            if let Some(color_material) = materials.get_mut(color_handle) {
                 color_material.color = config.tile_selected_color;
             }
         }
     }
 }
 
+fn on_hint_system(mut reader: EventReader<OnHint>, mut hinted_tile: ResMut<HintedTile>) {
+    if let Some(event) = reader.iter().last() {
+        hinted_tile.hint = Some(Hint {
+            target: event.target,
+            is_mine: event.is_mine,
+        });
+    }
+}
+
+fn clear_hint_system(
+    mut move_tile_reader: EventReader<OnMoveTile>,
+    mut undo_tile_reader: EventReader<OnUndoTile>,
+    mut reset_board_reader: EventReader<OnResetBoard>,
+    mut hinted_tile: ResMut<HintedTile>,
+) {
+    let is_board_changed = move_tile_reader.iter().count()
+        + undo_tile_reader.iter().count()
+        + reset_board_reader.iter().count()
+        > 0;
+    if is_board_changed && hinted_tile.hint.is_some() {
+        hinted_tile.hint = None;
+    }
+}
+
+fn update_heatmap_system(
+    mut toggle_reader: EventReader<OnToggleHeatmap>,
+    mut move_tile_reader: EventReader<OnMoveTile>,
+    mut undo_tile_reader: EventReader<OnUndoTile>,
+    mut reset_board_reader: EventReader<OnResetBoard>,
+    game_board: Res<model::GameBoard>,
+    mut heatmap: ResMut<Heatmap>,
+) {
+    let is_toggled = toggle_reader.iter().count() % 2 == 1;
+    let is_board_changed = move_tile_reader.iter().count()
+        + undo_tile_reader.iter().count()
+        + reset_board_reader.iter().count()
+        > 0;
+    if is_toggled {
+        heatmap.is_visible = !heatmap.is_visible;
+    }
+    if heatmap.is_visible && (is_toggled || is_board_changed) {
+        let probabilities = solver::mine_probabilities(&game_board);
+        if !probabilities.is_exact {
+            info!("heatmap is estimated: the frontier is too big to enumerate");
+        }
+        heatmap.probabilities = probabilities.tiles;
+    }
+}
+
 fn on_move_tile_system(
     mut reader: EventReader<OnMoveTile>,
     mut tile_text_query: Query<&mut Text>,
@@ -204,16 +354,14 @@ fn on_move_tile_system(
     sound: Res<ClickSound>,
 ) {
     for event in reader.iter() {
-        let target = match event {
-            OnMoveTile::Open { target } => target,
-            OnMoveTile::Flag { target } => target,
-        };
-        if let Some(tile_text_entity) = tile_ids.text_ids.get(target) {
-            if let Ok(mut tile_text) = tile_text_query.get_mut(*tile_text_entity) {
-                let (value, color) = get_tile_text_and_color(&game_board, *target, &config);
-                tile_text.sections[0].value = value;
-                tile_text.sections[0].style.color = color;
-            }
+        for target in event.targets() {
+            update_tile_text(
+                &mut tile_text_query,
+                &tile_ids,
+                &game_board,
+                *target,
+                &config,
+            );
         }
 
         audio.play(sound.0.clone());
@@ -224,10 +372,13 @@ fn get_tile_text_and_color(
     game_board: &model::GameBoard,
     grid: PointyHexGrid,
     config: &Config,
+    reveal_wrong_flags: bool,
 ) -> (String, Color) {
     if let Some(tile_state) = game_board.get(grid) {
         if tile_state.is_open() {
-            if tile_state.is_mine() {
+            if tile_state.is_defused() {
+                ("M".to_string(), config.tile_text_defused_color)
+            } else if tile_state.is_mine() {
                 ("M".to_string(), config.tile_text_mine_color)
             } else {
                 (
@@ -236,8 +387,12 @@ fn get_tile_text_and_color(
                 )
             }
         } else {
-            if tile_state.is_flag() {
+            if tile_state.is_flag() && !tile_state.is_mine() && reveal_wrong_flags {
+                ("X".to_string(), config.tile_text_wrong_flag_color)
+            } else if tile_state.is_flag() {
                 ("F".to_string(), config.tile_text_flag_color)
+            } else if tile_state.is_question() {
+                ("?".to_string(), config.tile_text_question_color)
             } else {
                 (" ".to_string(), Color::PINK)
             }
@@ -335,10 +490,10 @@ fn on_game_over_system(
         game_over_query.single_mut().is_visible = true;
         let mut game_over_text = game_over_text_query.single_mut();
         match event {
-            OnGameOver::Open { target } => {
+            OnGameOver::Open { .. } => {
                 game_over_text.sections[0].value = config.game_over_wrong_open_text.clone();
             }
-            OnGameOver::Flag { target } => {
+            OnGameOver::Flag { .. } => {
                 game_over_text.sections[0].value = config.game_over_wrong_flag_text.clone();
             }
         }
@@ -408,24 +563,53 @@ fn setup_game_clear(mut commands: Commands, config: Res<Config>, asset_server: R
                 ))),
                 ..Default::default()
             });
+            let game_clear_stats_text_style = TextStyle {
+                font: game_clear_text_font.clone(),
+                font_size: config.game_clear_stats_text_size,
+                color: config.game_clear_text_below_color,
+            };
+            parent.spawn((
+                GameClearStatsText,
+                Text2dBundle {
+                    text: Text::from_section("", game_clear_stats_text_style)
+                        .with_alignment(TextAlignment::CENTER),
+                    transform: Transform::from_translation(Vec3::from((
+                        config.game_clear_stats_text_position,
+                        config.game_clear_text_layer,
+                    ))),
+                    ..Default::default()
+                },
+            ));
         });
 }
 
+#[derive(Component)]
+struct GameClearStatsText;
+
 fn on_game_clear_system(
     mut reader: EventReader<OnGameClear>,
     mut game_clear_query: Query<&mut Visibility, With<GameClearParent>>,
+    mut game_clear_stats_text_query: Query<&mut Text, With<GameClearStatsText>>,
+    game_stats: Res<GameStats>,
     audio: Res<Audio>,
     sound: Res<GameClearSound>,
 ) {
     for _ in reader.iter() {
         let mut game_clear = game_clear_query.single_mut();
         game_clear.is_visible = true;
+        game_clear_stats_text_query.single_mut().sections[0].value = format!(
+            "Time: {:.1}s  3BV: {}  Clicks: {}  Efficiency: {:.0}%",
+            game_stats.elapsed,
+            game_stats.board_3bv,
+            game_stats.clicks(),
+            game_stats.efficiency() * 100.0
+        );
         audio.play(sound.0.clone());
     }
 }
 
 fn on_retry_system(
-    mut reader: EventReader<OnRetry>,
+    mut reader: EventReader<OnResetBoard>,
     mut game_over_query: Query<&mut Visibility, (With<GameOverParent>, Without<GameClearParent>)>,
     mut game_clear_query: Query<&mut Visibility, (With<GameClearParent>, Without<GameOverParent>)>,
 ) {
@@ -435,6 +619,82 @@ fn on_retry_system(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+fn on_reset_board_system(
+    mut commands: Commands,
+    mut reader: EventReader<OnResetBoard>,
+    tilemap_query: Query<Entity, With<Tilemap>>,
+    mut mines_hint_text_query: Query<&mut Text, With<MinesHintText>>,
+    config: Res<Config>,
+    game_board: Res<model::GameBoard>,
+    game_rule: Res<model::GameRule>,
+    windows: Res<Windows>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if reader.iter().last().is_none() {
+        return;
+    }
+
+    for tilemap in tilemap_query.iter() {
+        commands.entity(tilemap).despawn_recursive();
+    }
+    spawn_tilemap(
+        &mut commands,
+        &config,
+        &game_board,
+        &windows,
+        &asset_server,
+        &mut meshes,
+        &mut materials,
+    );
+    mines_hint_text_query.single_mut().sections[0].value = mines_hint_text(&game_board, &game_rule);
+}
+
+fn on_rescue_system(
+    mut reader: EventReader<OnRescue>,
+    mut game_over_query: Query<&mut Visibility, With<GameOverParent>>,
+    mut mines_hint_text_query: Query<&mut Text, With<MinesHintText>>,
+    game_board: Res<model::GameBoard>,
+    game_rule: Res<model::GameRule>,
+) {
+    for _ in reader.iter() {
+        game_over_query.single_mut().is_visible = false;
+        mines_hint_text_query.single_mut().sections[0].value =
+            mines_hint_text(&game_board, &game_rule);
+    }
+}
+
+/// Marks wrong flags when the game is lost or cleared and hides them again on a rescue.
+fn reveal_wrong_flags_system(
+    mut game_over_reader: EventReader<OnGameOver>,
+    mut game_clear_reader: EventReader<OnGameClear>,
+    mut rescue_reader: EventReader<OnRescue>,
+    mut tile_text_query: Query<&mut Text>,
+    tile_ids: Res<TileIds>,
+    game_board: Res<model::GameBoard>,
+    config: Res<Config>,
+) {
+    // 開けたタイルで勝つルールでは間違った旗が残ったままクリアになることがある
+    let is_ended =
+        game_over_reader.iter().last().is_some() | game_clear_reader.iter().last().is_some();
+    let is_rescued = rescue_reader.iter().last().is_some();
+    if !is_ended && !is_rescued {
+        return;
+    }
+    for grid in game_board.grids() {
+        update_tile_text_with_reveal(
+            &mut tile_text_query,
+            &tile_ids,
+            &game_board,
+            grid,
+            &config,
+            !is_rescued,
+        );
+    }
+}
+
 fn on_undo_tile_system(
     mut reader: EventReader<OnUndoTile>,
     mut tile_text_query: Query<&mut Text>,
@@ -443,16 +703,42 @@ fn on_undo_tile_system(
     config: Res<Config>,
 ) {
     for event in reader.iter() {
-        let target = match event {
-            OnUndoTile::UnOpen { target } => target,
-            OnUndoTile::UnFlag { target } => target,
-        };
-        if let Some(tile_text_entity) = tile_ids.text_ids.get(target) {
-            if let Ok(mut tile_text) = tile_text_query.get_mut(*tile_text_entity) {
-                let (value, color) = get_tile_text_and_color(&game_board, *target, &config);
-                tile_text.sections[0].value = value;
-                tile_text.sections[0].style.color = color;
-            }
+        for target in event.targets() {
+            update_tile_text(
+                &mut tile_text_query,
+                &tile_ids,
+                &game_board,
+                *target,
+                &config,
+            );
+        }
+    }
+}
+
+fn update_tile_text(
+    tile_text_query: &mut Query<&mut Text>,
+    tile_ids: &TileIds,
+    game_board: &model::GameBoard,
+    target: PointyHexGrid,
+    config: &Config,
+) {
+    update_tile_text_with_reveal(tile_text_query, tile_ids, game_board, target, config, false);
+}
+
+fn update_tile_text_with_reveal(
+    tile_text_query: &mut Query<&mut Text>,
+    tile_ids: &TileIds,
+    game_board: &model::GameBoard,
+    target: PointyHexGrid,
+    config: &Config,
+    reveal_wrong_flags: bool,
+) {
+    if let Some(tile_text_entity) = tile_ids.text_ids.get(&target) {
+        if let Ok(mut tile_text) = tile_text_query.get_mut(*tile_text_entity) {
+            let (value, color) =
+                get_tile_text_and_color(game_board, target, config, reveal_wrong_flags);
+            tile_text.sections[0].value = value;
+            tile_text.sections[0].style.color = color;
         }
     }
 }
@@ -481,6 +767,7 @@ fn setup_mines_hint_text(
     mut commands: Commands,
     config: Res<Config>,
     game_board: Res<model::GameBoard>,
+    game_rule: Res<model::GameRule>,
     asset_server: Res<AssetServer>,
 ) {
     let mines_hint_text_font = asset_server.load(&config.mines_hint_text_font_path);
@@ -493,11 +780,7 @@ fn setup_mines_hint_text(
         MinesHintText,
         Text2dBundle {
             text: Text::from_section(
-                format!(
-                    "Mines: {} (Remaining: {})",
-                    game_board.count_mines(),
-                    game_board.count_remaining_mines()
-                ),
+                mines_hint_text(&game_board, &game_rule),
                 mines_hint_text_style,
             )
             .with_alignment(TextAlignment::TOP_LEFT),
@@ -511,18 +794,430 @@ fn setup_mines_hint_text(
 }
 
 fn on_flag_tile_system(
-    mut reader: EventReader<OnMoveTile>,
+    mut move_reader: EventReader<OnMoveTile>,
+    mut undo_reader: EventReader<OnUndoTile>,
     mut query: Query<&mut Text, With<MinesHintText>>,
     game_board: Res<model::GameBoard>,
+    game_rule: Res<model::GameRule>,
 ) {
-    for event in reader.iter() {
-        if let OnMoveTile::Flag { target } = event {
-            let mut text = query.single_mut();
-            text.sections[0].value = format!(
-                "Mines: {} (Remaining: {})",
-                game_board.count_mines(),
-                game_board.count_remaining_mines()
-            );
+    let is_moved = move_reader.iter().last().is_some();
+    let is_undone = undo_reader.iter().last().is_some();
+    if is_moved || is_undone {
+        let mut text = query.single_mut();
+        text.sections[0].value = mines_hint_text(&game_board, &game_rule);
+    }
+}
+
+fn mines_hint_text(game_board: &model::GameBoard, game_rule: &model::GameRule) -> String {
+    let mut text = format!(
+        "Mines: {} (Remaining: {})",
+        game_board.count_mines(),
+        game_board
+            .count_mines()
+            .saturating_sub(game_board.count_flags() + game_board.count_defused_mines())
+    );
+    if game_rule.lives > 1 {
+        text += &format!(
+            "  Lives: {}/{}",
+            game_board.count_lives_left(game_rule.lives),
+            game_rule.lives
+        );
+    }
+    if game_board.rescue_count() > 0 {
+        text += &format!("  Rescues: {}", game_board.rescue_count());
+    }
+    text
+}
+
+#[derive(Component)]
+struct StatsText;
+
+fn setup_stats_text(mut commands: Commands, config: Res<Config>, asset_server: Res<AssetServer>) {
+    let stats_text_font = asset_server.load(&config.stats_text_font_path);
+    let stats_text_style = TextStyle {
+        font: stats_text_font,
+        font_size: config.stats_text_size,
+        color: config.stats_text_color,
+    };
+    commands.spawn((
+        StatsText,
+        Text2dBundle {
+            text: Text::from_section("", stats_text_style).with_alignment(TextAlignment::TOP_LEFT),
+            transform: Transform::from_translation(Vec3::from((
+                config.stats_text_position,
+                config.stats_text_layer,
+            ))),
+            ..Default::default()
+        },
+    ));
+}
+
+fn update_stats_text_system(
+    mut query: Query<&mut Text, With<StatsText>>,
+    game_stats: Res<GameStats>,
+) {
+    if !game_stats.is_changed() {
+        return;
+    }
+    let mut text = query.single_mut();
+    text.sections[0].value = format!(
+        "Time: {:.0}s  Opens: {}  Flags: {}  Undos: {}  Efficiency: {:.0}%",
+        game_stats.elapsed.floor(),
+        game_stats.opens,
+        game_stats.flags,
+        game_stats.undos,
+        game_stats.efficiency() * 100.0
+    );
+}
+
+#[derive(Component)]
+struct LevelSelectParent;
+
+#[derive(Component)]
+struct LevelSelectText;
+
+fn setup_level_select(mut commands: Commands, config: Res<Config>) {
+    commands
+        .spawn((
+            LevelSelectParent,
+            Transform::from_translation(Vec3::ZERO),
+            GlobalTransform::default(),
+            Visibility::INVISIBLE,
+            ComputedVisibility::INVISIBLE,
+        ))
+        .add_children(|parent| {
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: config.level_select_background_color,
+                    ..Default::default()
+                },
+                transform: Transform::from_scale(Vec3 {
+                    x: 10000.0,
+                    y: 10000.0,
+                    z: 1.0,
+                })
+                .with_translation(Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: config.level_select_background_layer,
+                }),
+                ..Default::default()
+            });
+            parent.spawn((
+                LevelSelectText,
+                Text2dBundle {
+                    text: Text::default().with_alignment(TextAlignment::TOP_CENTER),
+                    transform: Transform::from_translation(Vec3::from((
+                        config.level_select_text_position,
+                        config.level_select_text_layer,
+                    ))),
+                    ..Default::default()
+                },
+            ));
+        });
+}
+
+fn update_level_select_system(
+    game_scene: Res<GameScene>,
+    levels: Res<Levels>,
+    mut level_select_query: Query<&mut Visibility, With<LevelSelectParent>>,
+    mut level_select_text_query: Query<&mut Text, With<LevelSelectText>>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+) {
+    if !game_scene.is_changed() && !levels.is_changed() {
+        return;
+    }
+
+    let is_visible = matches!(*game_scene, GameScene::LevelSelect);
+    level_select_query.single_mut().is_visible = is_visible;
+    if !is_visible {
+        return;
+    }
+
+    let font = asset_server.load(&config.level_select_text_font_path);
+    let style = |color| TextStyle {
+        font: font.clone(),
+        font_size: config.level_select_text_size,
+        color,
+    };
+    let mut sections = vec![TextSection::new(
+        format!("{}\n\n", config.level_select_title),
+        style(config.level_select_text_color),
+    )];
+    for (i, board) in levels.boards.iter().enumerate() {
+        let color = if i == levels.cursor {
+            config.level_select_text_cursor_color
+        } else {
+            config.level_select_text_color
+        };
+        let mut label = board.name.clone();
+        if let Some(title) = &board.title {
+            label += &format!(": {}", title);
+        }
+        let mut details = format!("{}, {} mines", board.shape, board.mine_count);
+        if let Some(difficulty) = &board.difficulty {
+            details += &format!(", {}", difficulty);
+        }
+        sections.push(TextSection::new(
+            format!(
+                "{}{}  ({})\n",
+                if i == levels.cursor { "> " } else { "" },
+                label,
+                details
+            ),
+            style(color),
+        ));
+    }
+    level_select_text_query.single_mut().sections = sections;
+}
+
+#[derive(Component)]
+struct DifficultyMenuParent;
+
+#[derive(Component)]
+struct DifficultyMenuText;
+
+fn setup_difficulty_menu(mut commands: Commands, config: Res<Config>) {
+    commands
+        .spawn((
+            DifficultyMenuParent,
+            Transform::from_translation(Vec3::ZERO),
+            GlobalTransform::default(),
+            Visibility::INVISIBLE,
+            ComputedVisibility::INVISIBLE,
+        ))
+        .add_children(|parent| {
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: config.difficulty_menu_background_color,
+                    ..Default::default()
+                },
+                transform: Transform::from_scale(Vec3 {
+                    x: 10000.0,
+                    y: 10000.0,
+                    z: 1.0,
+                })
+                .with_translation(Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: config.difficulty_menu_background_layer,
+                }),
+                ..Default::default()
+            });
+            parent.spawn((
+                DifficultyMenuText,
+                Text2dBundle {
+                    text: Text::default().with_alignment(TextAlignment::TOP_CENTER),
+                    transform: Transform::from_translation(Vec3::from((
+                        config.difficulty_menu_text_position,
+                        config.difficulty_menu_text_layer,
+                    ))),
+                    ..Default::default()
+                },
+            ));
+        });
+}
+
+fn update_difficulty_menu_system(
+    game_scene: Res<GameScene>,
+    menu: Res<DifficultyMenu>,
+    mut difficulty_menu_query: Query<&mut Visibility, With<DifficultyMenuParent>>,
+    mut difficulty_menu_text_query: Query<&mut Text, With<DifficultyMenuText>>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+) {
+    if !game_scene.is_changed() && !menu.is_changed() {
+        return;
+    }
+
+    let is_visible = matches!(*game_scene, GameScene::DifficultyMenu);
+    difficulty_menu_query.single_mut().is_visible = is_visible;
+    if !is_visible {
+        return;
+    }
+
+    let font = asset_server.load(&config.difficulty_menu_text_font_path);
+    let style = |color| TextStyle {
+        font: font.clone(),
+        font_size: config.difficulty_menu_text_size,
+        color,
+    };
+    let row_color = |is_cursor| {
+        if is_cursor {
+            config.difficulty_menu_text_cursor_color
+        } else {
+            config.difficulty_menu_text_color
+        }
+    };
+    let mut sections = vec![TextSection::new(
+        format!("{}\n\n", config.difficulty_menu_title),
+        style(config.difficulty_menu_text_color),
+    )];
+    for (i, preset) in PRESETS.iter().enumerate() {
+        let is_cursor = i == menu.cursor;
+        sections.push(TextSection::new(
+            format!(
+                "{}{}  (size {}, {:.0}% mines)\n",
+                if is_cursor { "> " } else { "" },
+                preset.name,
+                preset.tiles_per_side,
+                preset.density * 100.0
+            ),
+            style(row_color(is_cursor)),
+        ));
+    }
+    let is_cursor = menu.cursor == PRESETS.len();
+    sections.push(TextSection::new(
+        format!("{}Custom\n", if is_cursor { "> " } else { "" }),
+        style(row_color(is_cursor)),
+    ));
+    let on_off = |is_on| if is_on { "on" } else { "off" };
+    sections.push(TextSection::new(
+        format!(
+            "\ng: No guessing: {} / p: Practice: {} / l: Lives: {}\nf: Strict flags: {} / w: Win: {}\n",
+            on_off(menu.is_no_guess),
+            on_off(menu.rule.practice),
+            menu.rule.lives,
+            on_off(menu.rule.strict_flag),
+            menu.rule.win_rule.name()
+        ),
+        style(config.difficulty_menu_text_color),
+    ));
+
+    // カスタムの設定を開いているときだけ値を出す
+    if let Some(field) = menu.custom_field {
+        let custom = &menu.custom;
+        sections.push(TextSection::new(
+            "\n",
+            style(config.difficulty_menu_text_color),
+        ));
+        for row in custom.fields() {
+            let value = match row {
+                CustomField::Shape => custom.kind.name().to_string(),
+                CustomField::Size => custom.size.to_string(),
+                CustomField::Height => custom.height.to_string(),
+                CustomField::MineCount => {
+                    format!("{} / {}", custom.mine_count, custom.tile_count())
+                }
+            };
+            sections.push(TextSection::new(
+                format!(
+                    "{}{}: {}\n",
+                    if row == field { "> " } else { "" },
+                    custom.label(row),
+                    value
+                ),
+                style(row_color(row == field)),
+            ));
+        }
+        match custom.check() {
+            Ok(()) => sections.push(TextSection::new(
+                format!("\n{}", config.difficulty_menu_custom_controls),
+                style(config.difficulty_menu_text_color),
+            )),
+            Err(err) => sections.push(TextSection::new(
+                format!("\n{}", err),
+                style(config.difficulty_menu_text_error_color),
+            )),
         }
     }
+    difficulty_menu_text_query.single_mut().sections = sections;
+}
+
+#[derive(Component)]
+struct GhostCursor;
+
+#[derive(Component)]
+struct ReplayText;
+
+fn setup_replay_view(
+    mut commands: Commands,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        GhostCursor,
+        MaterialMesh2dBundle {
+            mesh: meshes
+                .add(shape::Circle::new(config.ghost_cursor_size).into())
+                .into(),
+            material: materials.add(ColorMaterial::from(config.ghost_cursor_color)),
+            visibility: Visibility::INVISIBLE,
+            ..Default::default()
+        },
+    ));
+
+    let replay_text_style = TextStyle {
+        font: asset_server.load(&config.replay_text_font_path),
+        font_size: config.replay_text_size,
+        color: config.replay_text_color,
+    };
+    commands.spawn((
+        ReplayText,
+        Text2dBundle {
+            text: Text::from_section("", replay_text_style)
+                .with_alignment(TextAlignment::BOTTOM_LEFT),
+            transform: Transform::from_translation(Vec3::from((
+                config.replay_text_position,
+                config.replay_text_layer,
+            ))),
+            visibility: Visibility::INVISIBLE,
+            ..Default::default()
+        },
+    ));
+}
+
+fn update_ghost_cursor_system(
+    game_scene: Res<GameScene>,
+    replay_player: Res<ReplayPlayer>,
+    tilemap_query: Query<&GlobalTransform, With<Tilemap>>,
+    mut ghost_cursor_query: Query<(&mut Transform, &mut Visibility), With<GhostCursor>>,
+    config: Res<Config>,
+) {
+    let (mut transform, mut visibility) = ghost_cursor_query.single_mut();
+    let cursor = match *game_scene {
+        GameScene::Replay => replay_player.cursor(),
+        _ => None,
+    };
+    visibility.is_visible = cursor.is_some();
+    if let Some(cursor) = cursor {
+        let position = tilemap_query
+            .single()
+            .transform_point(Vec3::from((cursor * config.tile_size, 0.0)));
+        transform.translation = Vec3::from((position.truncate(), config.ghost_cursor_layer));
+    }
+}
+
+fn update_replay_text_system(
+    game_scene: Res<GameScene>,
+    replay_player: Res<ReplayPlayer>,
+    mut query: Query<(&mut Text, &mut Visibility), With<ReplayText>>,
+    config: Res<Config>,
+) {
+    let (mut text, mut visibility) = query.single_mut();
+    visibility.is_visible = matches!(*game_scene, GameScene::Replay);
+    if !visibility.is_visible {
+        return;
+    }
+    let total = replay_player
+        .replay
+        .as_ref()
+        .map_or(0, |replay| replay.inputs.len());
+    text.sections[0].value = format!(
+        "Replay x{} {}/{}{}  {}",
+        replay_player.speed,
+        replay_player.next,
+        total,
+        if replay_player.is_paused {
+            " (paused)"
+        } else if replay_player.is_finished() {
+            " (end)"
+        } else {
+            ""
+        },
+        config.replay_text_controls
+    );
 }